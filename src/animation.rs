@@ -0,0 +1,247 @@
+use crate::params::SimParams;
+use crate::slime_moulds::MAX_BLUR_RADIUS;
+
+
+/// A [`SimParams`] field a track can drive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Param {
+    TurnSpeed,
+    MoveSpeed,
+    SenseDistance,
+    SensorAngle,
+    /// Rounded, every change rebuilds the pipelines.
+    SensorSize,
+    DecayRate,
+    DiffuseRate,
+    /// Rounded, every change rebuilds the pipelines.
+    BlurRadius,
+    DepositAmount,
+    MaxAge,
+    EnergyGain,
+    EnergyCost,
+    SplitEnergy,
+}
+
+impl Param {
+    /// Looks a parameter up by its `SimParams` field name.
+    pub fn from_name(name: &str) -> Option<Param> {
+        Some(match name {
+            "turn_speed" => Param::TurnSpeed,
+            "move_speed" => Param::MoveSpeed,
+            "sense_distance" => Param::SenseDistance,
+            "sensor_angle" => Param::SensorAngle,
+            "sensor_size" => Param::SensorSize,
+            "decay_rate" => Param::DecayRate,
+            "diffuse_rate" => Param::DiffuseRate,
+            "blur_radius" => Param::BlurRadius,
+            "deposit_amount" => Param::DepositAmount,
+            "max_age" => Param::MaxAge,
+            "energy_gain" => Param::EnergyGain,
+            "energy_cost" => Param::EnergyCost,
+            "split_energy" => Param::SplitEnergy,
+            _ => return None,
+        })
+    }
+
+    /// Sizes, rates and energies are clamped at 0, cubic tracks can overshoot below their keys. The
+    /// blur radius is also kept within what the blur passes can do.
+    pub fn set(&self, params: &mut SimParams, value: f32) {
+        match self {
+            Param::TurnSpeed => params.turn_speed = value,
+            Param::MoveSpeed => params.move_speed = value,
+            Param::SenseDistance => params.sense_distance = value,
+            Param::SensorAngle => params.sensor_angle = value,
+            Param::SensorSize => params.sensor_size = (value.round() as i32).max(0),
+            Param::DecayRate => params.decay_rate = value.max(0.0),
+            Param::DiffuseRate => params.diffuse_rate = value.max(0.0),
+            Param::BlurRadius => params.blur_radius = (value.round() as i32).clamp(0, MAX_BLUR_RADIUS),
+            Param::DepositAmount => params.deposit_amount = value.max(0.0),
+            Param::MaxAge => params.max_age = value.max(0.0),
+            Param::EnergyGain => params.energy_gain = value.max(0.0),
+            Param::EnergyCost => params.energy_cost = value.max(0.0),
+            Param::SplitEnergy => params.split_energy = value.max(0.0),
+        }
+    }
+}
+
+
+/// How a track moves from a keyframe to the next one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    /// Eases in and out, flat at both keyframes.
+    Smoothstep,
+    /// Catmull-Rom through the neighbouring keyframes, smooth across keyframes.
+    Cubic,
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    pub frame: u32,
+    pub value: f32,
+    /// Used between this keyframe and the next.
+    pub interpolation: Interpolation,
+}
+
+
+/// Keyframes for one parameter, the value holds still before the first and after the last.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track {
+    pub param: Param,
+    /// Sorted by frame.
+    keys: Vec<Keyframe>,
+}
+
+impl Track {
+    pub fn new(param: Param) -> Self {
+        Track { param, keys: Vec::new() }
+    }
+
+    /// Adds a keyframe, replacing any already on the same frame.
+    pub fn insert(&mut self, key: Keyframe) {
+        match self.keys.binary_search_by_key(&key.frame, |k| k.frame) {
+            Ok(index) => self.keys[index] = key,
+            Err(index) => self.keys.insert(index, key),
+        }
+    }
+
+    pub fn value_at(&self, frame: u32) -> Option<f32> {
+        let first = self.keys.first()?;
+        let last = self.keys.last()?;
+        if frame <= first.frame {
+            return Some(first.value);
+        }
+        if frame >= last.frame {
+            return Some(last.value);
+        }
+
+        // the last key starting at or before the frame, there is always one after it
+        let index = self.keys.partition_point(|k| k.frame <= frame) - 1;
+        let (k0, k1) = (self.keys[index], self.keys[index + 1]);
+        let t = (frame - k0.frame) as f32 / (k1.frame - k0.frame) as f32;
+        Some(match k0.interpolation {
+            Interpolation::Linear => k0.value + (k1.value - k0.value) * t,
+            Interpolation::Smoothstep => k0.value + (k1.value - k0.value) * t * t * (3.0 - 2.0 * t),
+            Interpolation::Cubic => {
+                let before = self.keys[index.saturating_sub(1)].value;
+                let after = self.keys[(index + 2).min(self.keys.len() - 1)].value;
+                catmull_rom(before, k0.value, k1.value, after, t)
+            }
+        })
+    }
+}
+
+
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+
+/// Sets every parameter with a track to its value at `frame`, leaving the rest alone.
+pub fn animate(tracks: &[Track], frame: u32, params: &mut SimParams) {
+    for track in tracks {
+        if let Some(value) = track.value_at(frame) {
+            track.param.set(params, value);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn track(keys: &[(u32, f32, Interpolation)]) -> Track {
+        let mut track = Track::new(Param::DecayRate);
+        for &(frame, value, interpolation) in keys {
+            track.insert(Keyframe { frame, value, interpolation });
+        }
+        track
+    }
+
+
+    #[test]
+    fn empty_track_has_no_value() {
+        assert_eq!(Track::new(Param::DecayRate).value_at(0), None);
+    }
+
+    #[test]
+    fn holds_before_the_first_key_and_after_the_last() {
+        let track = track(&[(10, 1.0, Interpolation::Linear), (20, 3.0, Interpolation::Linear)]);
+        assert_eq!(track.value_at(0), Some(1.0));
+        assert_eq!(track.value_at(10), Some(1.0));
+        assert_eq!(track.value_at(20), Some(3.0));
+        assert_eq!(track.value_at(1000), Some(3.0));
+    }
+
+    #[test]
+    fn every_interpolation_meets_its_keys() {
+        for interpolation in [Interpolation::Linear, Interpolation::Smoothstep, Interpolation::Cubic] {
+            let track = track(&[(0, 1.0, interpolation), (10, 5.0, interpolation), (30, 2.0, interpolation)]);
+            assert_eq!(track.value_at(0), Some(1.0), "{interpolation:?}");
+            assert_eq!(track.value_at(10), Some(5.0), "{interpolation:?}");
+            assert_eq!(track.value_at(30), Some(2.0), "{interpolation:?}");
+            // just either side of a key
+            assert!((track.value_at(9).unwrap() - 5.0).abs() < 1.0, "{interpolation:?}");
+            assert!((track.value_at(11).unwrap() - 5.0).abs() < 1.0, "{interpolation:?}");
+        }
+    }
+
+    #[test]
+    fn interpolates_between_keys() {
+        let linear = track(&[(0, 0.0, Interpolation::Linear), (10, 10.0, Interpolation::Linear)]);
+        assert_eq!(linear.value_at(3), Some(3.0));
+
+        let smoothstep = track(&[(0, 0.0, Interpolation::Smoothstep), (10, 10.0, Interpolation::Smoothstep)]);
+        assert_eq!(smoothstep.value_at(5), Some(5.0));
+        assert!(smoothstep.value_at(1).unwrap() < 1.0);
+
+        // with only two keys the neighbours repeat the ends, so the curve is symmetric
+        let cubic = track(&[(0, 0.0, Interpolation::Cubic), (10, 10.0, Interpolation::Cubic)]);
+        assert_eq!(cubic.value_at(5), Some(5.0));
+    }
+
+    #[test]
+    fn key_on_the_same_frame_replaces_the_old_one() {
+        let track = track(&[(0, 1.0, Interpolation::Linear), (10, 2.0, Interpolation::Linear), (0, 4.0, Interpolation::Linear)]);
+        assert_eq!(track.value_at(0), Some(4.0));
+        assert_eq!(track.value_at(5), Some(3.0));
+    }
+
+    #[test]
+    fn cubic_overshoot_is_clamped() {
+        let track = {
+            let mut track = Track::new(Param::BlurRadius);
+            for (frame, value) in [(0, 0.0), (10, 0.0), (20, 10.0)] {
+                track.insert(Keyframe { frame, value, interpolation: Interpolation::Cubic });
+            }
+            track
+        };
+        // the curve dips below 0 between the two flat keys
+        assert!(track.value_at(5).unwrap() < 0.0);
+
+        let mut params = SimParams::DEFAULT;
+        track.param.set(&mut params, track.value_at(5).unwrap());
+        assert_eq!(params.blur_radius, 0);
+        Param::DecayRate.set(&mut params, -0.5);
+        assert_eq!(params.decay_rate, 0.0);
+    }
+
+    #[test]
+    fn blur_radius_is_kept_within_the_blur_passes_limit() {
+        let mut track = Track::new(Param::BlurRadius);
+        track.insert(Keyframe { frame: 0, value: 4.0, interpolation: Interpolation::Linear });
+        track.insert(Keyframe { frame: 10, value: 100.0, interpolation: Interpolation::Linear });
+
+        let mut params = SimParams::DEFAULT;
+        animate(&[track], 10, &mut params);
+        assert_eq!(params.blur_radius, MAX_BLUR_RADIUS);
+    }
+}
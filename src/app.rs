@@ -1,20 +1,31 @@
-use std::{sync::Arc};
+use std::{sync::Arc, time::Instant};
+use log::{info, debug, trace, warn};
 use vulkano::{
     command_buffer::allocator::StandardCommandBufferAllocator,
     descriptor_set::allocator::StandardDescriptorSetAllocator,
+    sync::{future::FenceSignalFuture, GpuFuture},
 };
 use vulkano_util::{
-    context::{VulkanoContext, VulkanoConfig},
+    context::VulkanoContext,
     window::{VulkanoWindows, WindowDescriptor},
 };
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use winit::{
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent},
     event_loop::{EventLoop, ControlFlow},
     platform::run_return::EventLoopExtRunReturn
 };
-use crate::slime_moulds::SlimeComputePipeline;
+use crate::slime_moulds::{LimitError, SlimeComputePipeline, request_device_features};
 use crate::render_pass::RenderPassOverFrame;
-use crate::{HEIGHT, WIDTH, SCALE};
+use crate::debug::vulkano_config;
+use crate::profiler::log_timings;
+use crate::params::SimParams;
+use crate::scene::Scene;
+use crate::presets::{PRESETS, PresetTransition};
+use crate::stats::{StatsLog, COVERAGE_THRESHOLD};
+use crate::network::{Network, NETWORK_THRESHOLD};
+use crate::gui::{control_panel, Gui, PanelActions, PanelControls, PanelReadouts};
+use crate::{HEIGHT, WIDTH, SCALE, FRAMES_IN_FLIGHT, SPAWN_RATE, SPAWN_RADIUS, NUM_AGENTS, PRESET_TRANSITION};
 
 
 
@@ -25,14 +36,220 @@ pub struct SlimeApp {
     pub command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
     pub descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
     pub pipeline: Option<(SlimeComputePipeline, RenderPassOverFrame)>,
+    pub frame_stats: FrameStats,
+    pub frames: FramesInFlight,
+    pub brush: SpawnBrush,
+    /// Time each GPU pass with timestamp queries and log the averages alongside the fps summary.
+    pub profile: bool,
+    /// Seeds the agents' random number generators.
+    pub seed: u64,
+    /// Run the simulation on the context's compute queue when it has its own queue family, so it
+    /// can overlap rendering. Otherwise everything goes through the graphics queue.
+    pub async_compute: bool,
+    /// Parameters the simulation opens with, change them afterwards through
+    /// [`SlimeComputePipeline::set_params`].
+    pub params: SimParams,
+    /// Emitters and sinks the simulation opens with, change them afterwards through
+    /// [`SlimeComputePipeline::set_scene`].
+    pub scene: Scene,
+    /// Index into [`PRESETS`] of the preset in use, or being blended to.
+    pub preset: usize,
+    transition: Option<PresetTransition>,
+    /// Places the agents when respawning or resizing.
+    spawn_rng: StdRng,
+    /// Population the control panel's resize button sets.
+    pub agent_target: u32,
+    /// Parameter overlay, created when the window opens unless `show_gui` is false.
+    pub gui: Option<Gui>,
+    pub show_gui: bool,
+    /// Trail statistics are reduced on the GPU and written here every step, if set before opening.
+    pub stats_log: Option<StatsLog>,
+}
+
+
+const MAX_DELTA_TIME: f32 = 0.1;
+
+
+/// Tracks the time between frames and writes a summary to the log once a second.
+pub struct FrameStats {
+    last_frame: Option<Instant>,
+    delta_time: f32,
+    last_report: Instant,
+    frames: u32,
+    total_ms: f32,
+    worst_ms: f32,
+    /// Average frame time over the last reported second.
+    average_ms: f32,
+}
+
+impl FrameStats {
+    /// Returns true on frames where the summary was logged.
+    pub fn tick(&mut self) -> bool {
+        let now = Instant::now();
+        if let Some(last) = self.last_frame {
+            self.delta_time = (now - last).as_secs_f32();
+            let frame_ms = self.delta_time * 1000.0;
+            trace!("frame time {frame_ms:.2}ms");
+            self.frames += 1;
+            self.total_ms += frame_ms;
+            self.worst_ms = self.worst_ms.max(frame_ms);
+        }
+        self.last_frame = Some(now);
+
+        if self.frames > 0 && (now - self.last_report).as_secs_f32() >= 1.0 {
+            let average = self.total_ms / self.frames as f32;
+            self.average_ms = average;
+            info!("{:.1} fps, frame time avg {average:.2}ms, worst {:.2}ms", 1000.0 / average, self.worst_ms);
+            self.last_report = now;
+            self.frames = 0;
+            self.total_ms = 0.0;
+            self.worst_ms = 0.0;
+            return true;
+        }
+        false
+    }
+
+    /// Seconds between the last two frames, capped so a stall such as dragging the window doesn't
+    /// make the agents jump.
+    pub fn delta_time(&self) -> f32 {
+        self.delta_time.min(MAX_DELTA_TIME)
+    }
+
+    /// Average frame time in milliseconds, updated once a second.
+    pub fn average_ms(&self) -> f32 {
+        self.average_ms
+    }
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        FrameStats {
+            last_frame: None,
+            delta_time: 1.0 / 60.0,
+            last_report: Instant::now(),
+            frames: 0,
+            total_ms: 0.0,
+            worst_ms: 0.0,
+            average_ms: 0.0,
+        }
+    }
+}
+
+/// Keeps a fence for each of the last `FRAMES_IN_FLIGHT` frames, so the CPU only waits on the GPU
+/// when it is about to reuse a frame slot whose resources may still be in use.
+pub struct FramesInFlight {
+    fences: Vec<Option<Arc<FenceSignalFuture<Box<dyn GpuFuture>>>>>,
+    next: usize,
+}
+
+impl FramesInFlight {
+    /// Waits for the oldest frame to finish and returns its slot for the next frame to use.
+    pub fn begin(&mut self) -> usize {
+        let frame = self.next;
+        if let Some(fence) = self.fences[frame].take() {
+            fence.wait(None).unwrap();
+        }
+        self.next = (frame + 1) % self.fences.len();
+        frame
+    }
+
+    /// Flushes the frame's work with a fence to wait on next time round, the returned future
+    /// carries on to present.
+    pub fn end(&mut self, frame: usize, after_future: Box<dyn GpuFuture>) -> Box<dyn GpuFuture> {
+        let fence = Arc::new(after_future.then_signal_fence_and_flush().unwrap());
+        self.fences[frame] = Some(fence.clone());
+        fence.boxed()
+    }
+}
+
+impl Default for FramesInFlight {
+    fn default() -> Self {
+        FramesInFlight {
+            fences: vec![None; FRAMES_IN_FLIGHT],
+            next: 0,
+        }
+    }
+}
+
+/// Spawns agents around the cursor while the left mouse button is held.
+pub struct SpawnBrush {
+    /// Cursor position in window pixels, `None` while it is outside the window.
+    cursor: Option<[f32; 2]>,
+    held: bool,
+    /// Fraction of an agent left over from the last frame, so low frame times still spawn.
+    carry: f32,
+    rng: StdRng,
+    /// Agents per second.
+    pub rate: f32,
+    /// Simulation pixels.
+    pub radius: f32,
+}
+
+impl SpawnBrush {
+    pub fn new(seed: u64) -> Self {
+        SpawnBrush {
+            cursor: None,
+            held: false,
+            carry: 0.0,
+            rng: StdRng::seed_from_u64(seed),
+            rate: SPAWN_RATE,
+            radius: SPAWN_RADIUS,
+        }
+    }
+
+    /// Agents to add this frame, in simulation coordinates, facing away from the cursor.
+    pub fn spawn(&mut self, delta_time: f32, window_size: [f32; 2], sim_size: [f32; 2]) -> Vec<([f32; 2], f32)> {
+        let cursor = match self.cursor {
+            Some(cursor) if self.held => cursor,
+            _ => {
+                self.carry = 0.0;
+                return Vec::new();
+            }
+        };
+        let centre = [
+            cursor[0] / window_size[0] * sim_size[0],
+            cursor[1] / window_size[1] * sim_size[1],
+        ];
+
+        self.carry += self.rate * delta_time;
+        let count = self.carry as usize;
+        self.carry -= count as f32;
+
+        (0..count)
+            .map(|_| {
+                let angle = self.rng.gen::<f32>() * std::f32::consts::TAU;
+                // square root keeps the disc evenly filled
+                let distance = self.rng.gen::<f32>().sqrt() * self.radius;
+                let position = [
+                    (centre[0] + angle.cos() * distance).clamp(0.0, sim_size[0] - 1.0),
+                    (centre[1] + angle.sin() * distance).clamp(0.0, sim_size[1] - 1.0),
+                ];
+                (position, angle)
+            })
+            .collect()
+    }
 }
 
 impl SlimeApp {
+    /// Rolling average GPU time of every pass in milliseconds, empty unless `profile` was set
+    /// before opening.
+    pub fn gpu_timings(&self) -> Vec<(&'static str, f32)> {
+        match &self.pipeline {
+            Some((compute, render)) => {
+                let mut timings = compute.pass_timings();
+                timings.extend(render.pass_timings());
+                timings
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Fails if the device can't run a simulation of this size with this many agents.
     pub fn open(
         &mut self,
         event_loop: &EventLoop<()>,
         agent_data: Vec<([f32; 2], f32)>
-    ) {
+    ) -> Result<(), LimitError> {
         let window_id = self.windows.create_window(
             event_loop,
             &self.context,
@@ -44,49 +261,77 @@ impl SlimeApp {
             },
             |_| {},
         );
-
+        let swapchain_format = self.windows.get_renderer(window_id).unwrap().swapchain_format();
+        // offset so the brush doesn't draw the same numbers as the agents' generator states
+        self.brush = SpawnBrush::new(self.seed.wrapping_add(1));
+        self.spawn_rng = StdRng::seed_from_u64(self.seed.wrapping_add(2));
+        info!("opened {WIDTH}x{HEIGHT} window, swapchain format {swapchain_format:?}");
+
+        let compute_queue = if self.async_compute {
+            self.context.compute_queue().clone()
+        } else {
+            self.context.graphics_queue().clone()
+        };
         let mut pipeline = SlimeComputePipeline::new(
             self,
-            self.context.graphics_queue().clone(),
+            compute_queue,
             [(WIDTH / SCALE) as u32, (HEIGHT / SCALE) as u32],
-            agent_data
-        );
-        let render_pass = RenderPassOverFrame::new(
+            agent_data,
+            self.params,
+        )?;
+        pipeline.set_scene(self.scene.clone());
+        if self.stats_log.is_some() {
+            pipeline.enable_stats(COVERAGE_THRESHOLD);
+        }
+        let mut render_pass = RenderPassOverFrame::new(
             self,
             self.context.graphics_queue().clone(),
-            self.windows.get_renderer(window_id).unwrap().swapchain_format(),
+            swapchain_format,
         );
 
         
 
+        if self.show_gui {
+            let window = self.windows.get_primary_window().unwrap();
+            self.gui = Some(Gui::new(
+                self,
+                event_loop,
+                window,
+                self.context.graphics_queue().clone(),
+                render_pass.gui_subpass(),
+            ));
+        }
+
         let window_renderer = self.windows.get_primary_renderer_mut().unwrap();
         match window_renderer.window_size() {
             [w, h] => {
                 if w == 0.0 || h == 0.0 {
-                    return;
+                    return Ok(());
                 }
             }
         }
 
         let before_pipeline_future = match window_renderer.acquire() {
             Err(e) => {
-                println!("{e}");
-                return;
+                warn!("failed to acquire swapchain image: {e}");
+                return Ok(());
             }
             Ok(future) => future,
         };
 
-        let after_compute = pipeline.init(before_pipeline_future);
+        let frame = self.frames.begin();
+        let after_compute = before_pipeline_future.join(pipeline.init(frame));
 
-        let color_image = pipeline.colour_image();
+        let color_image = pipeline.colour_image(frame);
         let target_image = window_renderer.swapchain_image_view();
 
         let after_render = render_pass
-            .render(after_compute, color_image, target_image);
+            .render(after_compute, color_image, target_image, frame, None);
 
-        window_renderer.present(after_render, true);
+        window_renderer.present(self.frames.end(frame, after_render), false);
 
         self.pipeline = Some((pipeline, render_pass));
+        Ok(())
     }
 }
 
@@ -103,12 +348,75 @@ pub fn handle_window_events (
         match &event {
             Event::WindowEvent{
                 window_id, event
-            } => match event {
-                WindowEvent::CloseRequested => {
-                    running = false;
-                    app.windows.remove_renderer(*window_id);
-                },
-                _ => ()
+            } => {
+                let consumed = match &mut app.gui {
+                    Some(gui) => gui.on_event(event),
+                    None => false,
+                };
+                match event {
+                    WindowEvent::CloseRequested => {
+                        debug!("window close requested");
+                        running = false;
+                        app.windows.remove_renderer(*window_id);
+                    },
+                    WindowEvent::CursorMoved { position, .. } => {
+                        app.brush.cursor = Some([position.x as f32, position.y as f32]);
+                    },
+                    WindowEvent::CursorLeft { .. } => app.brush.cursor = None,
+                    WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                        // releases always get through so the brush can't stick on over the gui
+                        app.brush.held = *state == ElementState::Pressed && !consumed;
+                    },
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::C),
+                            ..
+                        },
+                        ..
+                    } if !consumed => {
+                        if let Some((compute_pipeline, _)) = &mut app.pipeline {
+                            compute_pipeline.clear_agents();
+                        }
+                    },
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::P),
+                            ..
+                        },
+                        ..
+                    } if !consumed => app.next_preset(),
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::R),
+                            ..
+                        },
+                        ..
+                    } if !consumed => app.respawn(),
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::H),
+                            ..
+                        },
+                        ..
+                    } if !consumed => {
+                        if let Some(gui) = &mut app.gui {
+                            gui.visible = !gui.visible;
+                        }
+                    },
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::G),
+                            ..
+                        },
+                        ..
+                    } if !consumed => app.export_network(),
+                    _ => ()
+                }
             },
             Event::MainEventsCleared => *control_flow = ControlFlow::Exit,
 
@@ -150,32 +458,114 @@ pub fn compute_then_render(
     }
 
     let (compute_pipeline, render_pipeline) = app.pipeline.as_mut().unwrap();
+    let reported = app.frame_stats.tick();
 
     let before_pipeline_future = match window_renderer.acquire() {
         Err(e) => {
-            println!("{e}");
+            warn!("failed to acquire swapchain image: {e}");
             return;
         }
         Ok(future) => future,
     };
 
-    let after_compute = compute_pipeline.compute(before_pipeline_future);
+    let spawned = app.brush.spawn(
+        app.frame_stats.delta_time(),
+        window_renderer.window_size(),
+        [WIDTH / SCALE, HEIGHT / SCALE],
+    );
+    if let Err(e) = compute_pipeline.append_agents(&spawned) {
+        warn!("can't spawn more agents: {e}");
+    }
+
+    if let Some(transition) = &mut app.transition {
+        compute_pipeline.set_params(transition.advance(app.frame_stats.delta_time()));
+        if transition.finished() {
+            app.transition = None;
+        }
+    }
+
+    let mut actions = PanelActions::default();
+    if let Some(gui) = &mut app.gui {
+        let mut params = *compute_pipeline.params();
+        let mut timings = compute_pipeline.pass_timings();
+        timings.extend(render_pipeline.pass_timings());
+        let readouts = PanelReadouts {
+            frame_ms: app.frame_stats.average_ms(),
+            agents: compute_pipeline.agent_count(),
+            capacity: compute_pipeline.agent_capacity(),
+            max_agents: compute_pipeline.max_agents(),
+            timings: &timings,
+        };
+        gui.run(window_renderer.window(), |context| {
+            actions = control_panel(
+                context,
+                PanelControls {
+                    params: &mut params,
+                    spawn_rate: &mut app.brush.rate,
+                    spawn_radius: &mut app.brush.radius,
+                    agent_target: &mut app.agent_target,
+                    preset: app.preset,
+                },
+                &readouts,
+            );
+        });
+        compute_pipeline.set_params(params);
+    }
 
-    let color_image = compute_pipeline.colour_image();
+    let frame = app.frames.begin();
+    // the step only waits on earlier steps, the acquired image is only needed by the render
+    let after_compute = before_pipeline_future.join(compute_pipeline.compute(app.frame_stats.delta_time(), frame));
+    // read back at the start of the step, so from a step a few frames ago
+    if let (Some(log), Some(stats)) = (&mut app.stats_log, compute_pipeline.trail_stats()) {
+        if let Err(e) = log.record(stats) {
+            warn!("can't write trail statistics: {e}");
+        }
+    }
+
+    let color_image = compute_pipeline.colour_image(frame);
     let target_image = window_renderer.swapchain_image_view();
 
     let after_render = render_pipeline
-        .render(after_compute, color_image, target_image);
+        .render(after_compute, color_image, target_image, frame, app.gui.as_mut());
+
+    // the renderer doesn't wait, FramesInFlight bounds how far ahead the CPU gets
+    window_renderer.present(app.frames.end(frame, after_render), false);
 
-    window_renderer.present(after_render, true);
+    if reported && app.profile {
+        log_timings(&app.gpu_timings());
+    }
+
+    if let Some(index) = actions.select_preset {
+        app.select_preset(index);
+    }
+    if actions.respawn {
+        app.respawn();
+    }
+    if actions.clear {
+        if let Some((compute_pipeline, _)) = &mut app.pipeline {
+            compute_pipeline.clear_agents();
+        }
+    }
+    if actions.resize {
+        app.resize_agents();
+    }
 }
 
 
 
 
-impl Default for SlimeApp {
-    fn default() -> Self {
-        let context = VulkanoContext::new(VulkanoConfig::default());
+impl SlimeApp {
+    /// `debug` turns on the validation layers and routes their messages to the logger.
+    pub fn new(debug: bool) -> Self {
+        let mut config = vulkano_config(debug);
+        request_device_features(&mut config);
+        let context = VulkanoContext::new(config);
+        info!("using device {} ({:?})", context.device_name(), context.device_type());
+        debug!(
+            "graphics queue family {}, compute queue family {}",
+            context.graphics_queue().queue_family_index(),
+            context.compute_queue().queue_family_index()
+        );
         let command_allocator = Arc::new(StandardCommandBufferAllocator::new(
             context.device().clone(),
             Default::default()
@@ -190,9 +580,98 @@ impl Default for SlimeApp {
             windows: VulkanoWindows::default(),
             command_buffer_allocator: command_allocator,
             descriptor_set_allocator: descript_allocator,
-            pipeline: None
+            pipeline: None,
+            frame_stats: FrameStats::default(),
+            frames: FramesInFlight::default(),
+            brush: SpawnBrush::new(0),
+            profile: false,
+            seed: 0,
+            async_compute: true,
+            params: SimParams::default(),
+            scene: Scene::default(),
+            preset: 0,
+            transition: None,
+            spawn_rng: StdRng::seed_from_u64(0),
+            agent_target: NUM_AGENTS as u32,
+            gui: None,
+            show_gui: true,
+            stats_log: None,
+        }
+    }
+
+    /// Blends the simulation over to preset `index` of [`PRESETS`] in `PRESET_TRANSITION` seconds,
+    /// starting from wherever it currently is. The agents are left as they are.
+    pub fn select_preset(&mut self, index: usize) {
+        let preset = &PRESETS[index];
+        info!("switching to preset {}", preset.name);
+        self.preset = index;
+        match &self.pipeline {
+            Some((compute_pipeline, _)) => {
+                self.transition = Some(PresetTransition::new(*compute_pipeline.params(), preset.params, PRESET_TRANSITION));
+            }
+            None => self.params = preset.params,
+        }
+    }
+
+    pub fn next_preset(&mut self) {
+        self.select_preset((self.preset + 1) % PRESETS.len());
+    }
+
+    /// Replaces the swarm with a fresh one placed by the current preset's spawn mode.
+    pub fn respawn(&mut self) {
+        let Some((compute_pipeline, _)) = &mut self.pipeline else {
+            return;
+        };
+        let agents = PRESETS[self.preset].spawn.spawn(&mut self.spawn_rng, NUM_AGENTS, [WIDTH / SCALE, HEIGHT / SCALE]);
+        compute_pipeline.clear_agents();
+        if let Err(e) = compute_pipeline.append_agents(&agents) {
+            warn!("can't respawn agents: {e}");
+        }
+        debug!("respawned with preset {}", PRESETS[self.preset].name);
+    }
+
+    /// Grows or shrinks the swarm to `agent_target`, placing any new agents by the current preset's
+    /// spawn mode.
+    pub fn resize_agents(&mut self) {
+        let Some((compute_pipeline, _)) = &mut self.pipeline else {
+            return;
+        };
+        let spawn = PRESETS[self.preset].spawn;
+        let rng = &mut self.spawn_rng;
+        let result = compute_pipeline.resize_agents(self.agent_target, |count| spawn.spawn(rng, count, [WIDTH / SCALE, HEIGHT / SCALE]));
+        match result {
+            Ok(()) => debug!("resized to {} agents", self.agent_target),
+            Err(e) => warn!("can't resize to {} agents: {e}", self.agent_target),
         }
     }
+
+    /// Extracts the network from the trail as it is now and writes it to `network_<step>.graphml`
+    /// and `network_<step>.json` in the working directory. Stalls until the GPU catches up.
+    pub fn export_network(&mut self) {
+        let Some((compute_pipeline, _)) = &mut self.pipeline else {
+            return;
+        };
+        let strength: Vec<f32> = compute_pipeline.download_trail().iter().map(|pixel| pixel[3]).collect();
+        let size = [(WIDTH / SCALE) as u32, (HEIGHT / SCALE) as u32];
+        let network = Network::extract(&strength, size, NETWORK_THRESHOLD);
+        info!(
+            "network: {} nodes, {} edges, total length {:.0}, {} cycles, degrees {:?}",
+            network.nodes.len(), network.edges.len(), network.total_length(), network.cycles(), network.degree_distribution()
+        );
+
+        let path = format!("network_{}", compute_pipeline.frame());
+        match network.save(&path) {
+            Ok(()) => info!("wrote {path}.graphml and {path}.json"),
+            Err(e) => warn!("can't write the network to {path}: {e}"),
+        }
+    }
+}
+
+
+impl Default for SlimeApp {
+    fn default() -> Self {
+        SlimeApp::new(false)
+    }
 }
 
 
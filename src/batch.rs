@@ -0,0 +1,597 @@
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+use log::{info, warn};
+use rand::{rngs::StdRng, SeedableRng};
+use crate::app::SlimeApp;
+use crate::animation::Param;
+use crate::params::SimParams;
+use crate::presets::SpawnMode;
+use crate::scene::Scene;
+use crate::slime_moulds::{SlimeComputePipeline, MAX_BLUR_RADIUS};
+use crate::stats::{TrailStats, COVERAGE_THRESHOLD};
+use crate::{HEIGHT, WIDTH, SCALE, NUM_AGENTS, FRAMES_IN_FLIGHT};
+
+
+/// Seconds every step of a run without a window advances the simulation by, the main loop's frame
+/// rate.
+pub const STEP_TIME: f32 = 1.0 / 60.0;
+const DEFAULT_STEPS: u32 = 600;
+/// Widest a cell's picture can be, the trail is shrunk by a whole factor to fit.
+const CELL_WIDTH: u32 = 384;
+/// Space around and between the cells.
+const GAP: u32 = 8;
+/// Each pixel of the label font is drawn this many pixels across.
+const GLYPH_SCALE: u32 = 2;
+const BACKGROUND: [u8; 3] = [24, 24, 24];
+const LABEL_COLOUR: [u8; 3] = [220, 220, 220];
+
+
+/// Values to try for one parameter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepAxis {
+    pub param: Param,
+    pub name: String,
+    /// As given on the command line, so degrees for `sensor_angle`.
+    pub values: Vec<f32>,
+}
+
+impl std::str::FromStr for SweepAxis {
+    type Err = String;
+
+    /// `name=start:end:count` for evenly spaced values including both ends, or `name=a,b,c`.
+    fn from_str(text: &str) -> Result<SweepAxis, String> {
+        let (name, range) = text
+            .split_once('=')
+            .ok_or_else(|| format!("expected <param>=<start>:<end>:<count> or <param>=<a>,<b>,.., got '{text}'"))?;
+        let param = Param::from_name(name).ok_or_else(|| format!("unknown parameter '{name}'"))?;
+
+        let values = match range.split(':').collect::<Vec<_>>()[..] {
+            [start, end, count] => {
+                let start = parse_number(start)?;
+                let end = parse_number(end)?;
+                let count: u32 = count
+                    .parse()
+                    .map_err(|_| format!("expected a number of values, got '{count}'"))?;
+                match count {
+                    0 => Vec::new(),
+                    1 => vec![start],
+                    _ => (0..count).map(|i| start + (end - start) * i as f32 / (count - 1) as f32).collect(),
+                }
+            }
+            [list] => list.split(',').map(parse_number).collect::<Result<_, _>>()?,
+            _ => return Err(format!("expected <start>:<end>:<count>, got '{range}'")),
+        };
+        if values.is_empty() {
+            return Err(format!("{name} has no values to sweep"));
+        }
+        // caught here rather than by the pipeline partway through the sweep
+        if param == Param::BlurRadius {
+            if let Some(value) = values.iter().find(|&&value| !(0..=MAX_BLUR_RADIUS).contains(&(value.round() as i32))) {
+                return Err(format!("blur_radius has to be between 0 and {MAX_BLUR_RADIUS}, got {value}"));
+            }
+        }
+        Ok(SweepAxis { param, name: name.to_string(), values })
+    }
+}
+
+
+/// Every combination of a few parameters, each run headless from the same start.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sweep {
+    /// The first varies across the contact sheet's columns, the rest down its rows.
+    pub axes: Vec<SweepAxis>,
+    /// Steps each combination runs for.
+    pub steps: u32,
+    /// Directory the contact sheet and manifests are written to.
+    pub output: PathBuf,
+}
+
+impl Sweep {
+    /// Reads a sweep from `--sweep=<axis>` (two or more), `--steps=<n>` and `--out=<dir>`, or
+    /// `None` if there aren't any `--sweep` arguments.
+    pub fn from_args(args: &[String]) -> Result<Option<Sweep>, String> {
+        let axes = args
+            .iter()
+            .filter_map(|arg| arg.strip_prefix("--sweep="))
+            .map(str::parse)
+            .collect::<Result<Vec<SweepAxis>, _>>()?;
+        if axes.is_empty() {
+            return Ok(None);
+        }
+        if axes.len() < 2 {
+            return Err("a sweep needs at least two --sweep parameters".to_string());
+        }
+        for (i, axis) in axes.iter().enumerate() {
+            if axes[..i].iter().any(|other| other.param == axis.param) {
+                return Err(format!("{} is swept more than once", axis.name));
+            }
+        }
+
+        let steps = match args.iter().find_map(|arg| arg.strip_prefix("--steps=")) {
+            Some(steps) => steps.parse().map_err(|_| format!("--steps expects a number, got '{steps}'"))?,
+            None => DEFAULT_STEPS,
+        };
+        let output = args
+            .iter()
+            .find_map(|arg| arg.strip_prefix("--out="))
+            .unwrap_or("sweep")
+            .into();
+        Ok(Some(Sweep { axes, steps, output }))
+    }
+
+    pub fn num_cells(&self) -> usize {
+        self.axes.iter().map(|axis| axis.values.len()).product()
+    }
+
+    pub fn columns(&self) -> usize {
+        self.axes[0].values.len()
+    }
+
+    /// The value of each axis for cell `index`, counting across each row of the sheet in turn.
+    pub fn cell_values(&self, index: usize) -> Vec<f32> {
+        let mut stride = 1;
+        self.axes
+            .iter()
+            .map(|axis| {
+                let value = axis.values[index / stride % axis.values.len()];
+                stride *= axis.values.len();
+                value
+            })
+            .collect()
+    }
+}
+
+
+/// Measured from the trail and agents at the end of a cell's run, by the stats passes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CellMetrics {
+    pub alive: u32,
+    /// Average trail strength over every pixel.
+    pub mean_trail: f32,
+    pub max_trail: f32,
+    /// Fraction of pixels with a trail of at least `COVERAGE_THRESHOLD`.
+    pub coverage: f32,
+}
+
+impl CellMetrics {
+    fn new(stats: &TrailStats, alive: u32) -> Self {
+        CellMetrics {
+            alive,
+            mean_trail: stats.mean,
+            max_trail: stats.max,
+            coverage: stats.coverage,
+        }
+    }
+}
+
+
+/// Runs every combination in `sweep` from the app's seed, parameters and scene, then writes
+/// `contact_sheet.png`, `manifest.csv` and `manifest.json` to the sweep's output directory. The
+/// scene's keyframes for the swept parameters are left out.
+pub fn run_sweep(app: &SlimeApp, sweep: &Sweep, preset_name: &str, spawn: SpawnMode) -> io::Result<()> {
+    let size = [(WIDTH / SCALE) as u32, (HEIGHT / SCALE) as u32];
+    let mut sheet = ContactSheet::new(sweep, size);
+    let mut cells = Vec::with_capacity(sweep.num_cells());
+
+    // a keyframed track would overwrite the swept value before the first step
+    let mut scene = app.scene.clone();
+    scene.tracks.retain(|track| {
+        let swept = sweep.axes.iter().find(|axis| axis.param == track.param);
+        if let Some(axis) = swept {
+            warn!("ignoring the scene's keyframes for {}, it's being swept", axis.name);
+        }
+        swept.is_none()
+    });
+
+    for index in 0..sweep.num_cells() {
+        let values = sweep.cell_values(index);
+        let mut params = app.params;
+        for (axis, &value) in sweep.axes.iter().zip(&values) {
+            let value = if axis.param == Param::SensorAngle { value.to_radians() } else { value };
+            axis.param.set(&mut params, value);
+        }
+
+        let mut pipeline = headless_pipeline(app, size, spawn, params, scene.clone())?;
+        pipeline.enable_stats(COVERAGE_THRESHOLD);
+        run_steps(&mut pipeline, sweep.steps);
+
+        let trail = pipeline.download_trail();
+        let alive = pipeline.download_agents().len() as u32;
+        // without any steps the trail is still clear
+        let metrics = pipeline
+            .finish_stats()
+            .map_or(CellMetrics { alive, ..Default::default() }, |stats| CellMetrics::new(stats, alive));
+        info!(
+            "sweep cell {}/{}: {alive} agents, mean trail {:.3}, coverage {:.1}%",
+            index + 1, sweep.num_cells(), metrics.mean_trail, metrics.coverage * 100.0
+        );
+
+        let labels = sweep
+            .axes
+            .iter()
+            .zip(&values)
+            .map(|(axis, &value)| format!("{}={}", axis.name, format_value(value)))
+            .collect::<Vec<_>>();
+        sheet.draw_cell(index, &trail, size[0], &labels);
+        cells.push((values, metrics));
+    }
+
+    fs::create_dir_all(&sweep.output)?;
+    sheet.save(sweep.output.join("contact_sheet.png"))?;
+    fs::write(sweep.output.join("manifest.csv"), manifest_csv(sweep, &cells))?;
+    fs::write(sweep.output.join("manifest.json"), manifest_json(app, sweep, preset_name, &cells))?;
+    info!("wrote {} cells to {}", cells.len(), sweep.output.display());
+    Ok(())
+}
+
+
+/// A cleared simulation of `size` pixels to run without a window, starting from the same agents in
+/// the same places for every run with the app's seed.
+pub fn headless_pipeline(
+    app: &SlimeApp,
+    size: [u32; 2],
+    spawn: SpawnMode,
+    params: SimParams,
+    scene: Scene,
+) -> io::Result<SlimeComputePipeline> {
+    let mut rng = StdRng::seed_from_u64(app.seed);
+    let agents = spawn.spawn(&mut rng, NUM_AGENTS, [size[0] as f32, size[1] as f32]);
+    // nothing is drawn, so there's no render queue to hand the results over to
+    let mut pipeline = SlimeComputePipeline::new(app, app.context.graphics_queue().clone(), size, agents, params)
+        .map_err(io::Error::other)?;
+    pipeline.set_scene(scene);
+    pipeline.init(0);
+    Ok(pipeline)
+}
+
+
+/// Runs `steps` steps of `STEP_TIME` back to back, waiting for the gpu whenever every frame slot
+/// has a step in flight.
+pub fn run_steps(pipeline: &mut SlimeComputePipeline, steps: u32) {
+    for _ in 0..steps {
+        let frame = pipeline.frame() as usize % FRAMES_IN_FLIGHT;
+        if frame == 0 {
+            pipeline.wait();
+        }
+        pipeline.compute(STEP_TIME, frame);
+    }
+}
+
+
+fn manifest_csv(sweep: &Sweep, cells: &[(Vec<f32>, CellMetrics)]) -> String {
+    let mut csv = String::from("cell,row,column");
+    for axis in &sweep.axes {
+        write!(csv, ",{}", axis.name).unwrap();
+    }
+    csv.push_str(",alive,mean_trail,max_trail,coverage\n");
+
+    for (index, (values, metrics)) in cells.iter().enumerate() {
+        write!(csv, "{index},{},{}", index / sweep.columns(), index % sweep.columns()).unwrap();
+        for value in values {
+            write!(csv, ",{value}").unwrap();
+        }
+        writeln!(csv, ",{},{},{},{}", metrics.alive, metrics.mean_trail, metrics.max_trail, metrics.coverage).unwrap();
+    }
+    csv
+}
+
+fn manifest_json(app: &SlimeApp, sweep: &Sweep, preset_name: &str, cells: &[(Vec<f32>, CellMetrics)]) -> String {
+    let mut json = String::from("{\n");
+    writeln!(json, "  \"seed\": {},", app.seed).unwrap();
+    writeln!(json, "  \"preset\": \"{preset_name}\",").unwrap();
+    writeln!(json, "  \"steps\": {},", sweep.steps).unwrap();
+    writeln!(json, "  \"step_time\": {STEP_TIME},").unwrap();
+    writeln!(json, "  \"columns\": {},", sweep.columns()).unwrap();
+    json.push_str("  \"axes\": [\n");
+    for (i, axis) in sweep.axes.iter().enumerate() {
+        let values = axis.values.iter().map(f32::to_string).collect::<Vec<_>>().join(", ");
+        let comma = if i + 1 < sweep.axes.len() {","} else {""};
+        writeln!(json, "    {{\"param\": \"{}\", \"values\": [{values}]}}{comma}", axis.name).unwrap();
+    }
+    json.push_str("  ],\n  \"cells\": [\n");
+    for (index, (values, metrics)) in cells.iter().enumerate() {
+        let settings = sweep
+            .axes
+            .iter()
+            .zip(values)
+            .map(|(axis, value)| format!("\"{}\": {value}", axis.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let comma = if index + 1 < cells.len() {","} else {""};
+        writeln!(
+            json,
+            "    {{\"cell\": {index}, \"row\": {}, \"column\": {}, \"settings\": {{{settings}}}, \
+             \"metrics\": {{\"alive\": {}, \"mean_trail\": {}, \"max_trail\": {}, \"coverage\": {}}}}}{comma}",
+            index / sweep.columns(), index % sweep.columns(),
+            metrics.alive, metrics.mean_trail, metrics.max_trail, metrics.coverage
+        )
+        .unwrap();
+    }
+    json.push_str("  ]\n}\n");
+    json
+}
+
+/// At most three decimal places, without trailing zeros.
+fn format_value(value: f32) -> String {
+    let text = format!("{value:.3}");
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn parse_number(value: &str) -> Result<f32, String> {
+    value.parse().map_err(|_| format!("expected a number, got '{value}'"))
+}
+
+
+/// The sweep's cells laid out in a grid, each shrunk trail above its settings.
+struct ContactSheet {
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    /// Simulation pixels averaged into each side of a cell pixel.
+    shrink: u32,
+    cell_size: [u32; 2],
+    label_lines: u32,
+    columns: u32,
+}
+
+impl ContactSheet {
+    fn new(sweep: &Sweep, sim_size: [u32; 2]) -> Self {
+        let shrink = sim_size[0].div_ceil(CELL_WIDTH);
+        let cell_size = [sim_size[0] / shrink, sim_size[1] / shrink];
+        let label_lines = sweep.axes.len() as u32;
+        let columns = sweep.columns() as u32;
+        let rows = (sweep.num_cells() as u32).div_ceil(columns);
+
+        let width = GAP + columns * (cell_size[0] + GAP);
+        let height = GAP + rows * (cell_size[1] + label_lines * LINE_HEIGHT + GAP * 2);
+        let pixels = BACKGROUND.repeat((width * height) as usize);
+        ContactSheet { pixels, width, height, shrink, cell_size, label_lines, columns }
+    }
+
+    /// Shrinks the trail, `sim_width` pixels to a row, into the cell's place, with one line of label
+    /// under it per entry.
+    fn draw_cell(&mut self, index: usize, trail: &[[f32; 4]], sim_width: u32, labels: &[String]) {
+        let sim_width = sim_width as usize;
+        let column = index as u32 % self.columns;
+        let row = index as u32 / self.columns;
+        let left = GAP + column * (self.cell_size[0] + GAP);
+        let top = GAP + row * (self.cell_size[1] + self.label_lines * LINE_HEIGHT + GAP * 2);
+
+        // boxes of trail pixels averaged, then clamped and encoded as srgb like the swapchain would
+        let area = (self.shrink * self.shrink) as f32;
+        for y in 0..self.cell_size[1] {
+            for x in 0..self.cell_size[0] {
+                let mut colour = [0.0; 3];
+                for sy in 0..self.shrink {
+                    for sx in 0..self.shrink {
+                        let sim = ((y * self.shrink + sy) as usize) * sim_width + (x * self.shrink + sx) as usize;
+                        for (total, value) in colour.iter_mut().zip(trail[sim]) {
+                            *total += value;
+                        }
+                    }
+                }
+                self.set_pixel(left + x, top + y, colour.map(|c| linear_to_srgb(c / area)));
+            }
+        }
+
+        for (line, label) in labels.iter().enumerate() {
+            self.draw_text(left, top + self.cell_size[1] + GAP + line as u32 * LINE_HEIGHT, label);
+        }
+    }
+
+    /// Draws with the built in font, clipping anything running off the cell's right edge.
+    fn draw_text(&mut self, left: u32, top: u32, text: &str) {
+        let max_chars = (self.cell_size[0] / GLYPH_ADVANCE) as usize;
+        for (i, c) in text.chars().take(max_chars).enumerate() {
+            let rows = glyph(c);
+            let glyph_left = left + i as u32 * GLYPH_ADVANCE;
+            for (y, bits) in rows.iter().enumerate() {
+                for x in 0..3 {
+                    if bits & (0b100 >> x) == 0 {
+                        continue;
+                    }
+                    for dy in 0..GLYPH_SCALE {
+                        for dx in 0..GLYPH_SCALE {
+                            self.set_pixel(glyph_left + x * GLYPH_SCALE + dx, top + y as u32 * GLYPH_SCALE + dy, LABEL_COLOUR);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, colour: [u8; 3]) {
+        let i = ((y * self.width + x) * 3) as usize;
+        self.pixels[i..i + 3].copy_from_slice(&colour);
+    }
+
+    fn save(&self, path: PathBuf) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+}
+
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let encoded = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+
+/// Horizontal distance between the starts of neighbouring characters.
+const GLYPH_ADVANCE: u32 = 4 * GLYPH_SCALE;
+const LINE_HEIGHT: u32 = 7 * GLYPH_SCALE;
+
+/// 3x5 pixel rows, the highest bit on the left. Letters are all drawn as capitals, and anything
+/// missing is left blank.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_lowercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'a' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'b' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'c' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'd' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'e' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'f' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'g' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'h' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'i' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'j' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'k' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'l' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'm' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'n' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'o' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'p' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'r' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        's' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        't' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'u' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'v' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'w' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'x' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        _ => [0; 5],
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn sweep(axes: &[&str]) -> Sweep {
+        Sweep {
+            axes: axes.iter().map(|axis| axis.parse().unwrap()).collect(),
+            steps: DEFAULT_STEPS,
+            output: PathBuf::new(),
+        }
+    }
+
+
+    #[test]
+    fn axis_parses_ranges_and_lists() {
+        let axis: SweepAxis = "sensor_angle=20:90:8".parse().unwrap();
+        assert_eq!(axis.param, Param::SensorAngle);
+        assert_eq!(axis.name, "sensor_angle");
+        assert_eq!(axis.values, [20.0, 30.0, 40.0, 50.0, 60.0, 70.0, 80.0, 90.0]);
+
+        let axis: SweepAxis = "decay_rate=0.5:2:1".parse().unwrap();
+        assert_eq!(axis.values, [0.5]);
+
+        let axis: SweepAxis = "sense_distance=5,15,30".parse().unwrap();
+        assert_eq!(axis.param, Param::SenseDistance);
+        assert_eq!(axis.values, [5.0, 15.0, 30.0]);
+    }
+
+    #[test]
+    fn axis_rejects_bad_input() {
+        for text in [
+            "sensor_angle",
+            "colour=1,2",
+            "decay_rate=1:2",
+            "decay_rate=1:2:3:4",
+            "decay_rate=1:2:0",
+            "decay_rate=1:2:many",
+            "decay_rate=1,x",
+            "blur_radius=0,25",
+            "blur_radius=-1:4:3",
+        ] {
+            assert!(text.parse::<SweepAxis>().is_err(), "{text}");
+        }
+        assert!("blur_radius=0:24:4".parse::<SweepAxis>().is_ok());
+    }
+
+    #[test]
+    fn sweep_needs_two_different_axes() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(Sweep::from_args(&args(&["--steps=10"])), Ok(None));
+        assert!(Sweep::from_args(&args(&["--sweep=decay_rate=1,2"])).is_err());
+        assert!(Sweep::from_args(&args(&["--sweep=decay_rate=1,2", "--sweep=decay_rate=3,4"])).is_err());
+
+        let sweep = Sweep::from_args(&args(&["--sweep=decay_rate=1,2", "--sweep=blur_radius=0,1", "--steps=10", "--out=dir"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(sweep.steps, 10);
+        assert_eq!(sweep.output, PathBuf::from("dir"));
+    }
+
+    #[test]
+    fn cells_count_across_the_first_axis_then_down() {
+        let sweep = sweep(&["decay_rate=1,2,3", "blur_radius=0,1"]);
+        assert_eq!(sweep.num_cells(), 6);
+        assert_eq!(sweep.columns(), 3);
+        let cells: Vec<Vec<f32>> = (0..sweep.num_cells()).map(|index| sweep.cell_values(index)).collect();
+        assert_eq!(
+            cells,
+            [[1.0, 0.0], [2.0, 0.0], [3.0, 0.0], [1.0, 1.0], [2.0, 1.0], [3.0, 1.0]].map(|cell| cell.to_vec())
+        );
+    }
+
+    #[test]
+    fn contact_sheet_fits_every_cell_and_its_labels() {
+        let sweep = sweep(&["decay_rate=1,2,3", "blur_radius=0,1"]);
+        let sheet = ContactSheet::new(&sweep, [1000, 600]);
+        // 1000 pixels take a shrink of 3 to fit in CELL_WIDTH
+        assert_eq!(sheet.shrink, 3);
+        assert_eq!(sheet.cell_size, [333, 200]);
+        assert_eq!(sheet.width, GAP + 3 * (333 + GAP));
+        assert_eq!(sheet.height, GAP + 2 * (200 + 2 * LINE_HEIGHT + GAP * 2));
+        assert_eq!(sheet.pixels.len(), (sheet.width * sheet.height * 3) as usize);
+    }
+
+    #[test]
+    fn cells_are_read_with_the_trails_own_row_length() {
+        // 770 isn't a multiple of the shrink of 3, so each row has pixels left over at the end
+        let sim_size = [770, 3];
+        let sweep = sweep(&["decay_rate=1", "blur_radius=0"]);
+        let mut sheet = ContactSheet::new(&sweep, sim_size);
+        let trail: Vec<[f32; 4]> = (0..sim_size[0] * sim_size[1])
+            .map(|pixel| if pixel % sim_size[0] < 3 { [1.0; 4] } else { [0.0; 4] })
+            .collect();
+        sheet.draw_cell(0, &trail, sim_size[0], &[]);
+
+        let pixel = |x: u32, y: u32| {
+            let i = ((y * sheet.width + x) * 3) as usize;
+            [sheet.pixels[i], sheet.pixels[i + 1], sheet.pixels[i + 2]]
+        };
+        assert_eq!(pixel(GAP, GAP), [255; 3]);
+        assert_eq!(pixel(GAP + 1, GAP), [0; 3]);
+    }
+
+    #[test]
+    fn values_are_formatted_without_trailing_zeros() {
+        assert_eq!(format_value(2.0), "2");
+        assert_eq!(format_value(0.5), "0.5");
+        assert_eq!(format_value(1.0 / 3.0), "0.333");
+        assert_eq!(format_value(-12.25), "-12.25");
+    }
+}
@@ -0,0 +1,655 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use log::info;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use crate::app::SlimeApp;
+use crate::batch::{headless_pipeline, run_steps, STEP_TIME};
+use crate::network::{Edge, Network, Pieces, NETWORK_THRESHOLD};
+use crate::presets::SpawnMode;
+use crate::scene::Food;
+use crate::{HEIGHT, WIDTH, SCALE};
+
+
+/// Ten minutes of simulation.
+const DEFAULT_MAX_STEPS: u32 = 36000;
+/// Steps between extracting the network to see whether it has settled.
+const CHECK_INTERVAL: u32 = 300;
+/// The network counts as stable once its length has stayed within this fraction over
+/// `STABLE_CHECKS` checks in a row.
+const STABLE_TOLERANCE: f32 = 0.02;
+const STABLE_CHECKS: usize = 4;
+/// Simulation pixels kept clear between the food sources and the edges.
+const MARGIN: f32 = 80.0;
+/// How far from a food source, past its radius, the network can be and still count as reaching it.
+const REACH: f32 = 12.0;
+/// Edges each trial of the random removal takes out, and how many trials are averaged.
+const REMOVAL_FRACTION: f64 = 0.1;
+const REMOVAL_TRIALS: u32 = 100;
+
+
+/// A food source from the coordinate list.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Site {
+    pub name: String,
+    /// As given in the list.
+    pub coordinates: [f32; 2],
+}
+
+
+/// Food sources placed from a list of coordinates, with the simulation run until the network
+/// between them settles down.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Benchmark {
+    pub sites: Vec<Site>,
+    /// Steps to give up after if the network never settles.
+    pub max_steps: u32,
+    /// Directory the report and network are written to.
+    pub output: PathBuf,
+}
+
+impl Benchmark {
+    /// Reads a benchmark from `--benchmark=<path>`, `--max-steps=<n>` and `--out=<dir>`, or `None`
+    /// if there's no `--benchmark` argument. A sweep would share `--out`, so can't be asked for too.
+    pub fn from_args(args: &[String]) -> Result<Option<Benchmark>, String> {
+        let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("--benchmark=")) else {
+            return Ok(None);
+        };
+        if args.iter().any(|arg| arg.starts_with("--sweep=")) {
+            return Err("--benchmark and --sweep can't be run together".to_string());
+        }
+        let sites = load_sites(path)?;
+        let max_steps = match args.iter().find_map(|arg| arg.strip_prefix("--max-steps=")) {
+            Some(steps) => steps.parse().map_err(|_| format!("--max-steps expects a number, got '{steps}'"))?,
+            None => DEFAULT_MAX_STEPS,
+        };
+        let output = args
+            .iter()
+            .find_map(|arg| arg.strip_prefix("--out="))
+            .unwrap_or("benchmark")
+            .into();
+        Ok(Some(Benchmark { sites, max_steps, output }))
+    }
+
+    /// Where each site goes in a simulation of `size` pixels. The coordinates are scaled to fit
+    /// inside the margin keeping their aspect ratio, with y pointing up like a latitude.
+    pub fn positions(&self, size: [f32; 2]) -> Vec<[f32; 2]> {
+        let mut min = [f32::MAX; 2];
+        let mut max = [f32::MIN; 2];
+        for site in &self.sites {
+            for axis in 0..2 {
+                min[axis] = min[axis].min(site.coordinates[axis]);
+                max[axis] = max[axis].max(site.coordinates[axis]);
+            }
+        }
+        let extent = [max[0] - min[0], max[1] - min[1]];
+        let scale = ((size[0] - MARGIN * 2.0) / extent[0]).min((size[1] - MARGIN * 2.0) / extent[1]);
+        // all the sites on one spot or one line still get placed
+        let scale = if scale.is_finite() { scale } else { 0.0 };
+        let centre = [(min[0] + max[0]) * 0.5, (min[1] + max[1]) * 0.5];
+
+        self.sites
+            .iter()
+            .map(|site| {
+                [
+                    size[0] * 0.5 + (site.coordinates[0] - centre[0]) * scale,
+                    size[1] * 0.5 - (site.coordinates[1] - centre[1]) * scale,
+                ]
+            })
+            .collect()
+    }
+}
+
+
+/// One site per line as `x,y` or `name,x,y`, blank lines and lines starting with `#` are skipped.
+fn load_sites(path: impl AsRef<Path>) -> Result<Vec<Site>, String> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).map_err(|e| format!("can't read {}: {e}", path.display()))?;
+    let mut sites = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: String| format!("{} line {}: {message}", path.display(), index + 1);
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let (name, x, y) = match fields[..] {
+            [x, y] => (sites.len().to_string(), x, y),
+            [name, x, y] => (name.to_string(), x, y),
+            _ => return Err(error(format!("expected x,y or name,x,y, got '{line}'"))),
+        };
+        let parse = |value: &str| value.parse().map_err(|_| error(format!("expected a number, got '{value}'")));
+        sites.push(Site { name, coordinates: [parse(x)?, parse(y)?] });
+    }
+    if sites.len() < 2 {
+        return Err(format!("{} needs at least two sites", path.display()));
+    }
+    Ok(sites)
+}
+
+
+/// Runs the simulation with food at every site until the network between them stops changing or
+/// `max_steps` is reached, then measures it against the minimum spanning tree and the complete
+/// graph of the sites. Writes `report.json` and the network to the benchmark's output directory.
+pub fn run_benchmark(app: &SlimeApp, benchmark: &Benchmark, spawn: SpawnMode) -> io::Result<()> {
+    let size = [(WIDTH / SCALE) as u32, (HEIGHT / SCALE) as u32];
+    let positions = benchmark.positions([size[0] as f32, size[1] as f32]);
+    let mut scene = app.scene.clone();
+    scene.foods.extend(positions.iter().map(|&pos| Food { pos, ..Default::default() }));
+    let reach = Food::default().radius + REACH;
+
+    let mut pipeline = headless_pipeline(app, size, spawn, app.params, scene)?;
+
+    let mut step = 0;
+    let mut history = Vec::new();
+    let mut stable = false;
+    let (network, transport) = loop {
+        run_steps(&mut pipeline, CHECK_INTERVAL);
+        step += CHECK_INTERVAL;
+
+        let strength: Vec<f32> = pipeline.download_trail().iter().map(|pixel| pixel[3]).collect();
+        let network = Network::extract(&strength, size, NETWORK_THRESHOLD);
+        let transport = TransportGraph::from_network(&network, &positions, reach);
+        let length = transport.length();
+        info!(
+            "benchmark step {step}: network length {length:.0}, {} of {} food sources reached",
+            transport.reached(), positions.len()
+        );
+        history.push((step, length));
+
+        // the length settling is only a sign of stability once the network has reached something
+        let recent = &history[history.len().saturating_sub(STABLE_CHECKS + 1)..];
+        if recent.len() > STABLE_CHECKS && transport.reached() > 0 {
+            let (low, high) = recent
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(low, high), &(_, length)| (low.min(length), high.max(length)));
+            stable = high - low <= high * STABLE_TOLERANCE;
+        }
+        if stable || step >= benchmark.max_steps {
+            break (network, transport);
+        }
+    };
+    info!("network {} after {step} steps", if stable {"settled"} else {"still changing"});
+
+    let mut rng = StdRng::seed_from_u64(app.seed);
+    let measured = transport.measure(&positions, &mut rng);
+    let mst = TransportGraph::minimum_spanning_tree(&positions).measure(&positions, &mut rng);
+    let complete = TransportGraph::complete(&positions).measure(&positions, &mut rng);
+    info!(
+        "network cost {:.2}x the mst and {:.2}x the complete graph, efficiency {:.2}x the mst, fault tolerance {:.2}",
+        measured.length / mst.length, measured.length / complete.length,
+        measured.efficiency / mst.efficiency, measured.fault_tolerance
+    );
+
+    fs::create_dir_all(&benchmark.output)?;
+    network.save(benchmark.output.join("network"))?;
+    let report = Report { app, benchmark, positions: &positions, transport: &transport, step, stable, history: &history };
+    fs::write(benchmark.output.join("report.json"), report.to_json(&measured, &mst, &complete))?;
+    info!("wrote the benchmark to {}", benchmark.output.display());
+    Ok(())
+}
+
+
+/// How well a graph links the food sources, as in Tero et al., Rules for Biologically Inspired
+/// Adaptive Network Design, Science 2010.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TransportMetrics {
+    /// Total length of the edges, the cost of building the network.
+    pub length: f32,
+    /// Straight line distance over distance through the network, averaged over every pair of food
+    /// sources with pairs that aren't connected counting as zero. 1 for the complete graph.
+    pub efficiency: f32,
+    /// Fraction of single edge failures that don't disconnect any pair of food sources.
+    pub fault_tolerance: f32,
+    /// Fraction of the pairs of food sources still connected after taking out a random
+    /// `REMOVAL_FRACTION` of the edges, averaged over `REMOVAL_TRIALS` trials.
+    pub connectivity_after_removal: f32,
+}
+
+
+/// Weighted edges between nodes, some of which are the food sources.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransportGraph {
+    num_nodes: usize,
+    /// `(from, to, length)`.
+    edges: Vec<(usize, usize, f32)>,
+    /// The node for each food source and how far away it is, `None` if nothing reaches it.
+    terminals: Vec<Option<(usize, f32)>>,
+}
+
+impl TransportGraph {
+    /// Every food source joined straight to every other.
+    pub fn complete(positions: &[[f32; 2]]) -> Self {
+        let mut edges = Vec::new();
+        for a in 0..positions.len() {
+            for b in a + 1..positions.len() {
+                edges.push((a, b, distance(positions[a], positions[b])));
+            }
+        }
+        TransportGraph::between_sites(positions.len(), edges)
+    }
+
+    /// The shortest set of straight lines that connects every food source, by Prim's algorithm.
+    pub fn minimum_spanning_tree(positions: &[[f32; 2]]) -> Self {
+        let mut edges = Vec::new();
+        let mut in_tree = vec![false; positions.len()];
+        // the closest tree node to each node outside the tree
+        let mut closest: Vec<(f32, usize)> = vec![(f32::MAX, 0); positions.len()];
+        let mut next = 0;
+        for _ in 0..positions.len() {
+            in_tree[next] = true;
+            // the first node has nothing to join to yet
+            if closest[next].0 < f32::MAX {
+                edges.push((closest[next].1, next, closest[next].0));
+            }
+            for node in 0..positions.len() {
+                let length = distance(positions[next], positions[node]);
+                if !in_tree[node] && length < closest[node].0 {
+                    closest[node] = (length, next);
+                }
+            }
+            let Some(node) = (0..positions.len())
+                .filter(|&node| !in_tree[node])
+                .min_by(|&a, &b| closest[a].0.total_cmp(&closest[b].0))
+            else {
+                break;
+            };
+            next = node;
+        }
+        TransportGraph::between_sites(positions.len(), edges)
+    }
+
+    fn between_sites(num_sites: usize, edges: Vec<(usize, usize, f32)>) -> Self {
+        TransportGraph {
+            num_nodes: num_sites,
+            edges,
+            terminals: (0..num_sites).map(|site| Some((site, 0.0))).collect(),
+        }
+    }
+
+    /// The part of an extracted network that links the food sources. Each source is joined to the
+    /// nearest node within `reach`, and branches leading nowhere and pieces without any food are
+    /// cut off.
+    pub fn from_network(network: &Network, positions: &[[f32; 2]], reach: f32) -> Self {
+        let terminals: Vec<Option<(usize, f32)>> = positions
+            .iter()
+            .map(|&pos| {
+                network
+                    .nodes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, node)| (i, distance(pos, node.pos)))
+                    .filter(|&(_, length)| length <= reach)
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+            })
+            .collect();
+        let mut is_terminal = vec![false; network.nodes.len()];
+        for &(node, _) in terminals.iter().flatten() {
+            is_terminal[node] = true;
+        }
+
+        let mut alive = vec![true; network.edges.len()];
+        let mut node_edges = vec![Vec::new(); network.nodes.len()];
+        for (i, edge) in network.edges.iter().enumerate() {
+            node_edges[edge.from].push(i);
+            node_edges[edge.to].push(i);
+        }
+
+        // pieces of the network no food source is on
+        let mut reached = is_terminal.clone();
+        let mut stack: Vec<usize> = (0..network.nodes.len()).filter(|&node| is_terminal[node]).collect();
+        while let Some(node) = stack.pop() {
+            for &edge in &node_edges[node] {
+                let edge = network.edges[edge];
+                for other in [edge.from, edge.to] {
+                    if !reached[other] {
+                        reached[other] = true;
+                        stack.push(other);
+                    }
+                }
+            }
+        }
+        for (alive, edge) in alive.iter_mut().zip(&network.edges) {
+            *alive = reached[edge.from];
+        }
+
+        // dead ends without food, repeatedly, so whole branches go
+        let mut degree: Vec<usize> = node_edges
+            .iter()
+            .map(|edges| edges.iter().filter(|&&edge| alive[edge]).count())
+            .collect();
+        let mut leaves: Vec<usize> = (0..network.nodes.len()).filter(|&node| degree[node] == 1 && !is_terminal[node]).collect();
+        while let Some(node) = leaves.pop() {
+            let Some(&edge) = node_edges[node].iter().find(|&&edge| alive[edge]) else {
+                continue;
+            };
+            alive[edge] = false;
+            degree[node] -= 1;
+            let Edge { from, to, .. } = network.edges[edge];
+            let other = if from == node { to } else { from };
+            degree[other] -= 1;
+            if degree[other] == 1 && !is_terminal[other] {
+                leaves.push(other);
+            }
+        }
+
+        TransportGraph {
+            num_nodes: network.nodes.len(),
+            edges: network
+                .edges
+                .iter()
+                .zip(&alive)
+                .filter(|(_, &alive)| alive)
+                .map(|(edge, _)| (edge.from, edge.to, edge.length))
+                .collect(),
+            terminals,
+        }
+    }
+
+    pub fn length(&self) -> f32 {
+        self.edges.iter().map(|&(_, _, length)| length).sum()
+    }
+
+    /// Food sources the network reaches.
+    pub fn reached(&self) -> usize {
+        self.terminals.iter().flatten().count()
+    }
+
+    pub fn measure(&self, positions: &[[f32; 2]], rng: &mut StdRng) -> TransportMetrics {
+        let pairs = (positions.len() * positions.len().saturating_sub(1) / 2).max(1);
+
+        let mut efficiency = 0.0;
+        for a in 0..positions.len() {
+            let Some((from, from_offset)) = self.terminals[a] else {
+                continue;
+            };
+            let distances = self.shortest_paths(from);
+            for b in a + 1..positions.len() {
+                if let Some((to, to_offset)) = self.terminals[b] {
+                    let path = distances[to] + from_offset + to_offset;
+                    if path.is_finite() && path > 0.0 {
+                        efficiency += (distance(positions[a], positions[b]) / path).min(1.0);
+                    }
+                }
+            }
+        }
+
+        let everything = vec![true; self.edges.len()];
+        let connected = self.connected_pairs(&everything);
+        let mut survives = 0;
+        let mut kept = everything.clone();
+        for edge in 0..self.edges.len() {
+            kept[edge] = false;
+            if self.connected_pairs(&kept) == connected {
+                survives += 1;
+            }
+            kept[edge] = true;
+        }
+
+        let mut after_removal = 0.0;
+        for _ in 0..REMOVAL_TRIALS {
+            let kept: Vec<bool> = (0..self.edges.len()).map(|_| !rng.gen_bool(REMOVAL_FRACTION)).collect();
+            after_removal += self.connected_pairs(&kept) as f32 / pairs as f32;
+        }
+
+        TransportMetrics {
+            length: self.length(),
+            efficiency: efficiency / pairs as f32,
+            fault_tolerance: if self.edges.is_empty() { 0.0 } else { survives as f32 / self.edges.len() as f32 },
+            connectivity_after_removal: after_removal / REMOVAL_TRIALS as f32,
+        }
+    }
+
+    /// Dijkstra's algorithm, infinite for nodes that can't be reached.
+    fn shortest_paths(&self, from: usize) -> Vec<f32> {
+        let mut neighbours = vec![Vec::new(); self.num_nodes];
+        for &(a, b, length) in &self.edges {
+            neighbours[a].push((b, length));
+            neighbours[b].push((a, length));
+        }
+
+        let mut distances = vec![f32::INFINITY; self.num_nodes];
+        let mut queue = BinaryHeap::new();
+        distances[from] = 0.0;
+        queue.push(Visit { distance: 0.0, node: from });
+        while let Some(Visit { distance, node }) = queue.pop() {
+            if distance > distances[node] {
+                continue;
+            }
+            for &(next, length) in &neighbours[node] {
+                let through = distance + length;
+                if through < distances[next] {
+                    distances[next] = through;
+                    queue.push(Visit { distance: through, node: next });
+                }
+            }
+        }
+        distances
+    }
+
+    /// Pairs of food sources joined through the edges that are `kept`.
+    fn connected_pairs(&self, kept: &[bool]) -> usize {
+        let mut pieces = Pieces::new(self.num_nodes);
+        for (&(a, b, _), _) in self.edges.iter().zip(kept).filter(|(_, &kept)| kept) {
+            pieces.join(a, b);
+        }
+
+        let roots: Vec<Option<usize>> = self
+            .terminals
+            .iter()
+            .map(|terminal| terminal.map(|(node, _)| pieces.root(node)))
+            .collect();
+        let mut pairs = 0;
+        for a in 0..roots.len() {
+            for b in a + 1..roots.len() {
+                if roots[a].is_some() && roots[a] == roots[b] {
+                    pairs += 1;
+                }
+            }
+        }
+        pairs
+    }
+}
+
+
+/// A node waiting in Dijkstra's queue, the closest comes out first.
+struct Visit {
+    distance: f32,
+    node: usize,
+}
+
+impl PartialEq for Visit {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Visit {}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt()
+}
+
+
+/// Everything `report.json` is written from.
+struct Report<'a> {
+    app: &'a SlimeApp,
+    benchmark: &'a Benchmark,
+    positions: &'a [[f32; 2]],
+    transport: &'a TransportGraph,
+    step: u32,
+    stable: bool,
+    history: &'a [(u32, f32)],
+}
+
+impl Report<'_> {
+    fn to_json(&self, network: &TransportMetrics, mst: &TransportMetrics, complete: &TransportMetrics) -> String {
+        let metrics = |metrics: &TransportMetrics| {
+            format!(
+                "{{\"length\": {}, \"efficiency\": {}, \"fault_tolerance\": {}, \"connectivity_after_removal\": {}}}",
+                metrics.length, metrics.efficiency, metrics.fault_tolerance, metrics.connectivity_after_removal
+            )
+        };
+
+        let mut json = String::from("{\n");
+        writeln!(json, "  \"seed\": {},", self.app.seed).unwrap();
+        writeln!(json, "  \"steps\": {},", self.step).unwrap();
+        writeln!(json, "  \"step_time\": {STEP_TIME},").unwrap();
+        writeln!(json, "  \"stable\": {},", self.stable).unwrap();
+        json.push_str("  \"sites\": [\n");
+        for (i, (site, pos)) in self.benchmark.sites.iter().zip(self.positions).enumerate() {
+            let comma = if i + 1 < self.positions.len() {","} else {""};
+            writeln!(
+                json,
+                "    {{\"name\": \"{}\", \"coordinates\": [{}, {}], \"pos\": [{}, {}], \"reached\": {}}}{comma}",
+                site.name.replace('\\', "\\\\").replace('"', "\\\""),
+                site.coordinates[0], site.coordinates[1], pos[0], pos[1],
+                self.transport.terminals[i].is_some()
+            )
+            .unwrap();
+        }
+        json.push_str("  ],\n");
+        writeln!(json, "  \"network\": {},", metrics(network)).unwrap();
+        writeln!(json, "  \"minimum_spanning_tree\": {},", metrics(mst)).unwrap();
+        writeln!(json, "  \"complete_graph\": {},", metrics(complete)).unwrap();
+        writeln!(
+            json,
+            "  \"relative\": {{\"cost_vs_mst\": {}, \"cost_vs_complete\": {}, \"efficiency_vs_mst\": {}, \"efficiency_vs_complete\": {}}},",
+            network.length / mst.length, network.length / complete.length,
+            network.efficiency / mst.efficiency, network.efficiency / complete.efficiency
+        )
+        .unwrap();
+        let history = self
+            .history
+            .iter()
+            .map(|(step, length)| format!("[{step}, {length}]"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(json, "  \"history\": [{history}]").unwrap();
+        json.push_str("}\n");
+        json
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Node;
+
+
+    /// A 3-4-5 right angled triangle.
+    const TRIANGLE: [[f32; 2]; 3] = [[0.0, 0.0], [300.0, 0.0], [0.0, 400.0]];
+
+
+    fn benchmark(coordinates: &[[f32; 2]]) -> Benchmark {
+        Benchmark {
+            sites: coordinates
+                .iter()
+                .enumerate()
+                .map(|(i, &coordinates)| Site { name: i.to_string(), coordinates })
+                .collect(),
+            max_steps: DEFAULT_MAX_STEPS,
+            output: PathBuf::new(),
+        }
+    }
+
+
+    #[test]
+    fn benchmark_and_sweep_are_rejected_together() {
+        let args = ["--benchmark=sites.csv", "--sweep=decay_rate=1,2", "--sweep=blur_radius=0,1"].map(String::from);
+        assert!(Benchmark::from_args(&args).is_err());
+        assert_eq!(Benchmark::from_args(&args[1..]), Ok(None));
+    }
+
+    #[test]
+    fn positions_fit_inside_the_margin_with_y_up() {
+        let positions = benchmark(&[[0.0, 0.0], [20.0, 10.0]]).positions([1000.0, 600.0]);
+        // the width is the tighter fit at 42 pixels a unit, the height is centred
+        assert_eq!(positions, [[80.0, 510.0], [920.0, 90.0]]);
+    }
+
+    #[test]
+    fn collinear_sites_are_still_placed() {
+        let positions = benchmark(&[[0.0, 5.0], [10.0, 5.0], [20.0, 5.0]]).positions([1000.0, 600.0]);
+        assert_eq!(positions, [[80.0, 300.0], [500.0, 300.0], [920.0, 300.0]]);
+
+        let positions = benchmark(&[[3.0, 4.0], [3.0, 4.0]]).positions([1000.0, 600.0]);
+        assert_eq!(positions, [[500.0, 300.0], [500.0, 300.0]]);
+    }
+
+    #[test]
+    fn minimum_spanning_tree_leaves_out_the_longest_side() {
+        let mst = TransportGraph::minimum_spanning_tree(&TRIANGLE);
+        assert_eq!(mst.edges.len(), 2);
+        assert_eq!(mst.length(), 700.0);
+        assert_eq!(TransportGraph::complete(&TRIANGLE).length(), 1200.0);
+    }
+
+    #[test]
+    fn tree_has_no_fault_tolerance_and_complete_graph_is_fully_tolerant() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mst = TransportGraph::minimum_spanning_tree(&TRIANGLE).measure(&TRIANGLE, &mut rng);
+        let complete = TransportGraph::complete(&TRIANGLE).measure(&TRIANGLE, &mut rng);
+        assert_eq!(mst.fault_tolerance, 0.0);
+        assert_eq!(complete.fault_tolerance, 1.0);
+        assert!(complete.connectivity_after_removal >= mst.connectivity_after_removal);
+    }
+
+    #[test]
+    fn efficiency_goes_through_the_shortest_path() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let complete = TransportGraph::complete(&TRIANGLE).measure(&TRIANGLE, &mut rng);
+        assert_eq!(complete.efficiency, 1.0);
+        // the hypotenuse's 500 goes round the other two sides instead
+        let mst = TransportGraph::minimum_spanning_tree(&TRIANGLE).measure(&TRIANGLE, &mut rng);
+        assert!((mst.efficiency - (2.0 + 500.0 / 700.0) / 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn shortest_paths_take_the_detour_when_it_is_shorter() {
+        let graph = TransportGraph {
+            num_nodes: 4,
+            edges: vec![(0, 1, 10.0), (1, 2, 10.0), (0, 2, 50.0)],
+            terminals: Vec::new(),
+        };
+        assert_eq!(graph.shortest_paths(0), [0.0, 10.0, 20.0, f32::INFINITY]);
+    }
+
+    #[test]
+    fn from_network_prunes_dead_ends_and_pieces_without_food() {
+        // a square with a food source at each corner, a spur off one side and an island
+        let nodes = [[0.0, 0.0], [100.0, 0.0], [100.0, 100.0], [0.0, 100.0], [150.0, 50.0], [300.0, 300.0], [320.0, 300.0]]
+            .map(|pos| Node { pos, degree: 0 })
+            .to_vec();
+        let edge = |from, to, length| Edge { from, to, length, width: 1.0 };
+        let network = Network {
+            nodes,
+            edges: vec![edge(0, 1, 100.0), edge(1, 2, 100.0), edge(2, 3, 100.0), edge(3, 0, 100.0), edge(2, 4, 70.0), edge(5, 6, 20.0)],
+            ..Default::default()
+        };
+        let sites = [[0.0, 0.0], [105.0, 0.0], [100.0, 100.0], [0.0, 100.0], [500.0, 500.0]];
+
+        let transport = TransportGraph::from_network(&network, &sites, 20.0);
+        assert_eq!(transport.length(), 400.0);
+        assert_eq!(transport.reached(), 4);
+        assert_eq!(transport.terminals[1], Some((1, 5.0)));
+        assert_eq!(transport.terminals[4], None);
+    }
+}
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+use log::{error, warn, info, trace, LevelFilter};
+use vulkano::{
+    instance::{
+        debug::{DebugUtilsMessengerCreateInfo, DebugUtilsMessageSeverity, DebugUtilsMessageType, Message, ValidationFeatureEnable},
+        InstanceExtensions,
+    },
+    VulkanLibrary,
+};
+use vulkano_util::context::VulkanoConfig;
+
+
+const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+
+/// Builds the context config, enabling the khronos validation layer and a debug messenger that
+/// forwards to the logger when `debug` is set.
+pub fn vulkano_config(debug: bool) -> VulkanoConfig {
+    let mut config = VulkanoConfig::default();
+    if !debug {
+        return config;
+    }
+
+    let library = VulkanLibrary::new().unwrap();
+    let has_layer = library
+        .layer_properties()
+        .unwrap()
+        .any(|layer| layer.name() == VALIDATION_LAYER);
+    if !has_layer {
+        warn!("debug mode requested but {VALIDATION_LAYER} is not installed, continuing without validation");
+        return config;
+    }
+
+    let supported = library
+        .supported_extensions_with_layers([VALIDATION_LAYER])
+        .unwrap();
+
+    config.instance_create_info.enabled_layers = vec![VALIDATION_LAYER.to_string()];
+    config.instance_create_info.enabled_extensions = config.instance_create_info.enabled_extensions.union(&InstanceExtensions {
+        ext_debug_utils: true,
+        ..InstanceExtensions::empty()
+    });
+
+    // synchronisation validation catches the hazards between the compute passes and the render pass
+    if supported.ext_validation_features {
+        config.instance_create_info.enabled_extensions.ext_validation_features = true;
+        config.instance_create_info.enabled_validation_features = vec![ValidationFeatureEnable::SynchronizationValidation];
+    }
+
+    config.debug_create_info = Some(DebugUtilsMessengerCreateInfo {
+        message_severity: severity_for(log::max_level()),
+        message_type: DebugUtilsMessageType::GENERAL
+            | DebugUtilsMessageType::VALIDATION
+            | DebugUtilsMessageType::PERFORMANCE,
+        ..DebugUtilsMessengerCreateInfo::user_callback(Arc::new(log_message))
+    });
+
+    config
+}
+
+
+/// Only ask the layer for the messages the logger would actually print.
+fn severity_for(level: LevelFilter) -> DebugUtilsMessageSeverity {
+    let mut severity = DebugUtilsMessageSeverity::ERROR;
+    if level >= LevelFilter::Warn {
+        severity |= DebugUtilsMessageSeverity::WARNING;
+    }
+    if level >= LevelFilter::Info {
+        severity |= DebugUtilsMessageSeverity::INFO;
+    }
+    if level >= LevelFilter::Trace {
+        severity |= DebugUtilsMessageSeverity::VERBOSE;
+    }
+    severity
+}
+
+
+fn log_message(msg: &Message) {
+    let layer = msg.layer_prefix.unwrap_or("unknown");
+    let ty = if msg.ty.intersects(DebugUtilsMessageType::VALIDATION) {
+        "validation"
+    } else if msg.ty.intersects(DebugUtilsMessageType::PERFORMANCE) {
+        "performance"
+    } else {
+        "general"
+    };
+
+    if msg.severity.intersects(DebugUtilsMessageSeverity::ERROR) {
+        error!(target: "vulkan", "[{layer}] {ty}: {}", msg.description);
+    } else if msg.severity.intersects(DebugUtilsMessageSeverity::WARNING) {
+        warn!(target: "vulkan", "[{layer}] {ty}: {}", msg.description);
+    } else if msg.severity.intersects(DebugUtilsMessageSeverity::INFO) {
+        info!(target: "vulkan", "[{layer}] {ty}: {}", msg.description);
+    } else {
+        trace!(target: "vulkan", "[{layer}] {ty}: {}", msg.description);
+    }
+}
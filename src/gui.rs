@@ -0,0 +1,595 @@
+use std::{collections::HashMap, sync::Arc};
+use egui::{epaint::{ImageDelta, Primitive}, ClippedPrimitive, Context, ImageData, TextureFilter, TextureId, TexturesDelta};
+use log::{debug, warn};
+use vulkano::{
+    buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage},
+    command_buffer::{
+        allocator::StandardCommandBufferAllocator, AutoCommandBufferBuilder, BufferImageCopy,
+        CommandBufferInheritanceInfo, CommandBufferUsage, CopyBufferToImageInfo, PrimaryAutoCommandBuffer,
+        SecondaryAutoCommandBuffer,
+    },
+    descriptor_set::{allocator::StandardDescriptorSetAllocator, PersistentDescriptorSet, WriteDescriptorSet},
+    device::Queue,
+    format::{Format, NumericType},
+    image::{view::ImageView, ImageAccess, ImageCreateFlags, ImageDimensions, ImageUsage, StorageImage},
+    memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator},
+    pipeline::{
+        graphics::{
+            color_blend::{AttachmentBlend, BlendFactor, BlendOp, ColorBlendState},
+            input_assembly::InputAssemblyState,
+            vertex_input::Vertex,
+            viewport::{Scissor, Viewport, ViewportState},
+        },
+        GraphicsPipeline, Pipeline, PipelineBindPoint,
+    },
+    render_pass::Subpass,
+    sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
+};
+use winit::{event::WindowEvent, event_loop::EventLoopWindowTarget, window::Window};
+use crate::app::SlimeApp;
+use crate::params::SimParams;
+use crate::presets::PRESETS;
+use crate::slime_moulds::{RngAlgorithm, SensorKernel};
+
+
+#[derive(BufferContents, Vertex)]
+#[repr(C)]
+struct GuiVertex {
+    #[format(R32G32_SFLOAT)]
+    position: [f32; 2],
+    #[format(R32G32_SFLOAT)]
+    tex_coords: [f32; 2],
+    /// sRGB with premultiplied alpha, as egui hands it over.
+    #[format(R8G8B8A8_UNORM)]
+    colour: [u8; 4],
+}
+
+
+/// An egui overlay drawn in its own subpass on top of the simulation.
+///
+/// Feed it window events with [`Gui::on_event`], build the UI once a frame with [`Gui::run`], and
+/// the render pass draws whatever the last run produced.
+pub struct Gui {
+    context: Context,
+    state: egui_winit::State,
+    renderer: GuiRenderer,
+    primitives: Vec<ClippedPrimitive>,
+    textures_delta: TexturesDelta,
+    /// Toggled with `H`, a hidden overlay takes no input.
+    pub visible: bool,
+}
+
+impl Gui {
+    pub fn new<T>(
+        app: &SlimeApp,
+        event_loop: &EventLoopWindowTarget<T>,
+        window: &Window,
+        queue: Arc<Queue>,
+        subpass: Subpass,
+    ) -> Gui {
+        let mut state = egui_winit::State::new(event_loop);
+        state.set_pixels_per_point(egui_winit::native_pixels_per_point(window));
+        let max_image_size = queue.device().physical_device().properties().max_image_dimension2_d;
+        state.set_max_texture_side(max_image_size as usize);
+
+        Gui {
+            context: Context::default(),
+            state,
+            renderer: GuiRenderer::new(app, queue, subpass),
+            primitives: Vec::new(),
+            textures_delta: TexturesDelta::default(),
+            visible: true,
+        }
+    }
+
+    /// Returns true if the overlay used the event, so it shouldn't reach the simulation.
+    pub fn on_event(&mut self, event: &WindowEvent) -> bool {
+        self.visible && self.state.on_event(&self.context, event).consumed
+    }
+
+    /// Builds this frame's UI with `build_ui` and tessellates it ready to draw.
+    pub fn run(&mut self, window: &Window, build_ui: impl FnOnce(&Context)) {
+        if !self.visible {
+            self.primitives.clear();
+            return;
+        }
+        let input = self.state.take_egui_input(window);
+        let output = self.context.run(input, build_ui);
+        self.state.handle_platform_output(window, &self.context, output.platform_output);
+        self.primitives = self.context.tessellate(output.shapes);
+        self.textures_delta.append(output.textures_delta);
+    }
+
+    /// Records the texture uploads the last run asked for, which has to happen outside the render
+    /// pass.
+    pub fn upload(&mut self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer, Arc<StandardCommandBufferAllocator>>) {
+        for (id, delta) in std::mem::take(&mut self.textures_delta.set) {
+            self.renderer.set_texture(builder, id, delta);
+        }
+    }
+
+    /// Draws the last run's UI into the gui subpass, `viewport_dimensions` in physical pixels.
+    pub fn draw(&mut self, viewport_dimensions: [u32; 2]) -> SecondaryAutoCommandBuffer {
+        let command_buffer = self.renderer.draw(
+            viewport_dimensions,
+            self.context.pixels_per_point(),
+            &self.primitives,
+        );
+        // freed textures are still held by the command buffer until it has run
+        for id in std::mem::take(&mut self.textures_delta.free) {
+            self.renderer.textures.remove(&id);
+        }
+        command_buffer
+    }
+}
+
+
+struct GuiTexture {
+    image: Arc<StorageImage>,
+    descriptor_set: Arc<PersistentDescriptorSet>,
+}
+
+struct GuiRenderer {
+    queue: Arc<Queue>,
+    subpass: Subpass,
+    pipeline: Arc<GraphicsPipeline>,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
+    linear_sampler: Arc<Sampler>,
+    nearest_sampler: Arc<Sampler>,
+    textures: HashMap<TextureId, GuiTexture>,
+}
+
+impl GuiRenderer {
+    fn new(app: &SlimeApp, queue: Arc<Queue>, subpass: Subpass) -> GuiRenderer {
+        let device = queue.device().clone();
+        // the shaders blend in linear colour, which an sRGB target encodes on write
+        let output_srgb = subpass
+            .render_pass()
+            .attachments()[0]
+            .format
+            .map_or(false, |format| format.type_color() == Some(NumericType::SRGB));
+        debug!("gui output srgb {output_srgb}");
+
+        let pipeline = {
+            let vs = gui_vs::load(device.clone()).expect("failed to create shader module");
+            let fs = gui_fs::load(device.clone()).expect("failed to create shader module");
+            let blend = AttachmentBlend {
+                color_op: BlendOp::Add,
+                color_source: BlendFactor::One,
+                color_destination: BlendFactor::OneMinusSrcAlpha,
+                alpha_op: BlendOp::Add,
+                alpha_source: BlendFactor::OneMinusDstAlpha,
+                alpha_destination: BlendFactor::One,
+            };
+            GraphicsPipeline::start()
+                .vertex_input_state(GuiVertex::per_vertex())
+                .vertex_shader(vs.entry_point("main").unwrap(), ())
+                .input_assembly_state(InputAssemblyState::new())
+                .fragment_shader(
+                    fs.entry_point("main").unwrap(),
+                    gui_fs::SpecializationConstants { output_srgb: output_srgb as u32 },
+                )
+                .viewport_state(ViewportState::viewport_dynamic_scissor_dynamic(1))
+                .color_blend_state(ColorBlendState::new(1).blend(blend))
+                .render_pass(subpass.clone())
+                .build(device.clone())
+                .unwrap()
+        };
+
+        let sampler = |filter| {
+            Sampler::new(
+                device.clone(),
+                SamplerCreateInfo {
+                    mag_filter: filter,
+                    min_filter: filter,
+                    address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        GuiRenderer {
+            queue,
+            subpass,
+            pipeline,
+            memory_allocator: app.context.memory_allocator().clone(),
+            command_buffer_allocator: app.command_buffer_allocator.clone(),
+            descriptor_set_allocator: app.descriptor_set_allocator.clone(),
+            linear_sampler: sampler(Filter::Linear),
+            nearest_sampler: sampler(Filter::Nearest),
+            textures: HashMap::new(),
+        }
+    }
+
+    /// Creates texture `id`, or patches part of it, through a staging buffer.
+    fn set_texture(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer, Arc<StandardCommandBufferAllocator>>,
+        id: TextureId,
+        delta: ImageDelta,
+    ) {
+        let [width, height] = delta.image.size();
+        let pixels: Vec<u8> = match &delta.image {
+            ImageData::Color(image) => image.pixels.iter().flat_map(|colour| colour.to_array()).collect(),
+            ImageData::Font(image) => image.srgba_pixels(None).flat_map(|colour| colour.to_array()).collect(),
+        };
+        let staging = Buffer::from_iter(
+            &self.memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            pixels,
+        )
+        .unwrap();
+
+        let image = match delta.pos {
+            None => {
+                let image = StorageImage::with_usage(
+                    &self.memory_allocator,
+                    ImageDimensions::Dim2d {
+                        width: width as u32,
+                        height: height as u32,
+                        array_layers: 1,
+                    },
+                    Format::R8G8B8A8_SRGB,
+                    ImageUsage::SAMPLED | ImageUsage::TRANSFER_DST,
+                    ImageCreateFlags::empty(),
+                    [self.queue.queue_family_index()],
+                )
+                .unwrap();
+                let sampler = match delta.options.magnification {
+                    TextureFilter::Linear => self.linear_sampler.clone(),
+                    TextureFilter::Nearest => self.nearest_sampler.clone(),
+                };
+                let layout = self.pipeline.layout().set_layouts().get(0).unwrap();
+                let descriptor_set = PersistentDescriptorSet::new(
+                    &self.descriptor_set_allocator,
+                    layout.clone(),
+                    [WriteDescriptorSet::image_view_sampler(
+                        0,
+                        ImageView::new_default(image.clone()).unwrap(),
+                        sampler,
+                    )],
+                )
+                .unwrap();
+                self.textures.insert(id, GuiTexture { image: image.clone(), descriptor_set });
+                image
+            }
+            Some(_) => match self.textures.get(&id) {
+                Some(texture) => texture.image.clone(),
+                None => {
+                    warn!("gui patched texture {id:?} before creating it");
+                    return;
+                }
+            },
+        };
+
+        let [x, y] = delta.pos.unwrap_or([0, 0]);
+        builder
+            .copy_buffer_to_image(CopyBufferToImageInfo {
+                regions: [BufferImageCopy {
+                    image_subresource: image.subresource_layers(),
+                    image_offset: [x as u32, y as u32, 0],
+                    image_extent: [width as u32, height as u32, 1],
+                    ..Default::default()
+                }]
+                .into(),
+                ..CopyBufferToImageInfo::buffer_image(staging, image)
+            })
+            .unwrap();
+    }
+
+    fn draw(
+        &self,
+        viewport_dimensions: [u32; 2],
+        pixels_per_point: f32,
+        primitives: &[ClippedPrimitive],
+    ) -> SecondaryAutoCommandBuffer {
+        let mut builder = AutoCommandBufferBuilder::secondary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+            CommandBufferInheritanceInfo {
+                render_pass: Some(self.subpass.clone().into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let meshes: Vec<_> = primitives
+            .iter()
+            .filter_map(|primitive| match &primitive.primitive {
+                Primitive::Mesh(mesh) if !mesh.indices.is_empty() => Some((primitive.clip_rect, mesh)),
+                _ => None,
+            })
+            .collect();
+        if meshes.is_empty() {
+            return builder.build().unwrap();
+        }
+
+        // every mesh goes in one vertex and one index buffer, drawn with offsets into them
+        let vertices = meshes.iter().flat_map(|(_, mesh)| &mesh.vertices).map(|vertex| GuiVertex {
+            position: [vertex.pos.x, vertex.pos.y],
+            tex_coords: [vertex.uv.x, vertex.uv.y],
+            colour: vertex.color.to_array(),
+        });
+        let indices = meshes.iter().flat_map(|(_, mesh)| mesh.indices.iter().copied());
+        let upload = |usage| AllocationCreateInfo {
+            usage,
+            ..Default::default()
+        };
+        let vertex_buffer = Buffer::from_iter(
+            &self.memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            upload(MemoryUsage::Upload),
+            vertices.collect::<Vec<_>>(),
+        )
+        .unwrap();
+        let index_buffer = Buffer::from_iter(
+            &self.memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::INDEX_BUFFER,
+                ..Default::default()
+            },
+            upload(MemoryUsage::Upload),
+            indices.collect::<Vec<_>>(),
+        )
+        .unwrap();
+
+        let [width, height] = viewport_dimensions;
+        builder
+            .set_viewport(
+                0,
+                [Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [width as f32, height as f32],
+                    depth_range: 0.0..1.0,
+                }],
+            )
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .bind_vertex_buffers(0, vertex_buffer)
+            .bind_index_buffer(index_buffer)
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                gui_vs::PushConstants {
+                    screen_size: [width as f32 / pixels_per_point, height as f32 / pixels_per_point],
+                },
+            );
+
+        let mut first_index = 0;
+        let mut vertex_offset = 0;
+        for (clip_rect, mesh) in meshes {
+            let index_count = mesh.indices.len() as u32;
+            let vertex_count = mesh.vertices.len() as i32;
+
+            // clip rects are in points, scissors in pixels and entirely inside the framebuffer
+            let min_x = (clip_rect.min.x * pixels_per_point).round().clamp(0.0, width as f32) as u32;
+            let min_y = (clip_rect.min.y * pixels_per_point).round().clamp(0.0, height as f32) as u32;
+            let max_x = (clip_rect.max.x * pixels_per_point).round().clamp(min_x as f32, width as f32) as u32;
+            let max_y = (clip_rect.max.y * pixels_per_point).round().clamp(min_y as f32, height as f32) as u32;
+
+            match self.textures.get(&mesh.texture_id) {
+                Some(texture) if max_x > min_x && max_y > min_y => {
+                    builder
+                        .set_scissor(
+                            0,
+                            [Scissor {
+                                origin: [min_x, min_y],
+                                dimensions: [max_x - min_x, max_y - min_y],
+                            }],
+                        )
+                        .bind_descriptor_sets(
+                            PipelineBindPoint::Graphics,
+                            self.pipeline.layout().clone(),
+                            0,
+                            texture.descriptor_set.clone(),
+                        )
+                        .draw_indexed(index_count, 1, first_index, vertex_offset, 0)
+                        .unwrap();
+                }
+                Some(_) => (),
+                None => warn!("gui drew with unknown texture {:?}", mesh.texture_id),
+            }
+            first_index += index_count;
+            vertex_offset += vertex_count;
+        }
+
+        builder.build().unwrap()
+    }
+}
+
+
+/// Live view of the simulation for the control panel.
+pub struct PanelReadouts<'a> {
+    pub frame_ms: f32,
+    pub agents: u32,
+    pub capacity: u32,
+    /// Most agents the device can hold.
+    pub max_agents: u32,
+    /// Empty unless profiling.
+    pub timings: &'a [(&'static str, f32)],
+}
+
+/// What the control panel edits directly.
+pub struct PanelControls<'a> {
+    pub params: &'a mut SimParams,
+    pub spawn_rate: &'a mut f32,
+    pub spawn_radius: &'a mut f32,
+    /// Population the resize button sets.
+    pub agent_target: &'a mut u32,
+    pub preset: usize,
+}
+
+/// Things the control panel asks the app to do once it is drawn.
+#[derive(Default)]
+pub struct PanelActions {
+    pub select_preset: Option<usize>,
+    pub respawn: bool,
+    pub clear: bool,
+    pub resize: bool,
+}
+
+/// Sliders for every parameter, spawn controls and frame timings.
+pub fn control_panel(context: &Context, controls: PanelControls, readouts: &PanelReadouts) -> PanelActions {
+    let mut actions = PanelActions::default();
+    let params = controls.params;
+
+    egui::Window::new("Slime moulds").show(context, |ui| {
+        ui.label(format!("{:.1} fps, {:.2}ms", 1000.0 / readouts.frame_ms.max(0.001), readouts.frame_ms));
+        ui.label(format!("{} agents, room for {}", readouts.agents, readouts.capacity));
+        if !readouts.timings.is_empty() {
+            ui.collapsing("GPU timings", |ui| {
+                for (name, ms) in readouts.timings {
+                    ui.label(format!("{name}: {ms:.3}ms"));
+                }
+            });
+        }
+
+        ui.collapsing("Presets and spawning", |ui| {
+            egui::ComboBox::from_label("preset")
+                .selected_text(PRESETS[controls.preset].name)
+                .show_ui(ui, |ui| {
+                    for (index, preset) in PRESETS.iter().enumerate() {
+                        if ui.selectable_label(index == controls.preset, preset.name).clicked() {
+                            actions.select_preset = Some(index);
+                        }
+                    }
+                });
+            ui.horizontal(|ui| {
+                actions.respawn = ui.button("Respawn").clicked();
+                actions.clear = ui.button("Clear").clicked();
+            });
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(controls.agent_target).speed(1000.0).clamp_range(0..=readouts.max_agents));
+                actions.resize = ui.button("Resize").clicked();
+            });
+            ui.add(egui::Slider::new(controls.spawn_rate, 0.0..=200000.0).logarithmic(true).text("brush agents/s"));
+            ui.add(egui::Slider::new(controls.spawn_radius, 1.0..=300.0).text("brush radius"));
+        });
+
+        ui.collapsing("Movement", |ui| {
+            ui.add(egui::Slider::new(&mut params.turn_speed, 0.0..=200.0).text("turn speed"));
+            ui.add(egui::Slider::new(&mut params.move_speed, 0.0..=300.0).text("move speed"));
+            ui.add(egui::Slider::new(&mut params.sense_distance, 0.0..=100.0).text("sense distance"));
+            let mut degrees = params.sensor_angle.to_degrees();
+            if ui.add(egui::Slider::new(&mut degrees, 0.0..=180.0).suffix("°").text("sensor angle")).changed() {
+                params.sensor_angle = degrees.to_radians();
+            }
+            ui.add(egui::Slider::new(&mut params.sensor_size, 0..=10).text("sensor size"));
+            egui::ComboBox::from_label("sensor kernel")
+                .selected_text(format!("{:?}", params.sensor_kernel))
+                .show_ui(ui, |ui| {
+                    for kernel in [SensorKernel::Square, SensorKernel::Circle, SensorKernel::Gaussian] {
+                        ui.selectable_value(&mut params.sensor_kernel, kernel, format!("{kernel:?}"));
+                    }
+                });
+            ui.checkbox(&mut params.sub_pixel, "sub pixel");
+            egui::ComboBox::from_label("random numbers")
+                .selected_text(format!("{:?}", params.rng_algorithm))
+                .show_ui(ui, |ui| {
+                    for algorithm in [RngAlgorithm::Hash, RngAlgorithm::Pcg, RngAlgorithm::Xorshift] {
+                        ui.selectable_value(&mut params.rng_algorithm, algorithm, format!("{algorithm:?}"));
+                    }
+                });
+        });
+
+        ui.collapsing("Trail", |ui| {
+            ui.add(egui::Slider::new(&mut params.deposit_amount, 0.0..=300.0).text("deposit"));
+            ui.add(egui::Slider::new(&mut params.decay_rate, 0.0..=5.0).text("decay rate"));
+            ui.add(egui::Slider::new(&mut params.diffuse_rate, 0.0..=30.0).text("diffuse rate"));
+            ui.add(egui::Slider::new(&mut params.blur_radius, 0..=8).text("blur radius"));
+            ui.horizontal(|ui| {
+                ui.color_edit_button_rgb(&mut params.trail_colour);
+                ui.label("colour");
+            });
+        });
+
+        ui.collapsing("Lifecycle", |ui| {
+            ui.add(egui::Slider::new(&mut params.max_age, 0.0..=120.0).text("max age (0 off)"));
+            ui.add(egui::Slider::new(&mut params.energy_gain, 0.0..=10.0).text("energy gain"));
+            ui.add(egui::Slider::new(&mut params.energy_cost, 0.0..=2.0).text("energy cost"));
+            ui.add(egui::Slider::new(&mut params.split_energy, 0.0..=10.0).text("split energy (0 off)"));
+        });
+    });
+
+    actions
+}
+
+
+mod gui_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 450
+            layout(location = 0) in vec2 position;
+            layout(location = 1) in vec2 tex_coords;
+            layout(location = 2) in vec4 colour;
+
+            layout(location = 0) out vec4 v_colour;
+            layout(location = 1) out vec2 v_tex_coords;
+
+            // in points, the units egui lays out in
+            layout(push_constant) uniform PushConstants {
+                vec2 screen_size;
+            };
+
+            vec3 srgb_to_linear(vec3 srgb) {
+                bvec3 cutoff = lessThan(srgb, vec3(0.04045));
+                vec3 lower = srgb / 12.92;
+                vec3 higher = pow((srgb + 0.055) / 1.055, vec3(2.4));
+                return mix(higher, lower, cutoff);
+            }
+
+            void main() {
+                gl_Position = vec4(2.0 * position / screen_size - 1.0, 0.0, 1.0);
+                v_colour = vec4(srgb_to_linear(colour.rgb), colour.a);
+                v_tex_coords = tex_coords;
+            }
+        ",
+    }
+}
+
+mod gui_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 450
+            layout(location = 0) in vec4 v_colour;
+            layout(location = 1) in vec2 v_tex_coords;
+
+            layout(location = 0) out vec4 f_color;
+
+            layout(set = 0, binding = 0) uniform sampler2D font_texture;
+
+            // an sRGB target encodes on write, anything else needs it done here
+            layout(constant_id = 0) const bool output_srgb = true;
+
+            vec3 linear_to_srgb(vec3 linear) {
+                bvec3 cutoff = lessThan(linear, vec3(0.0031308));
+                vec3 lower = linear * 12.92;
+                vec3 higher = 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055;
+                return mix(higher, lower, cutoff);
+            }
+
+            void main() {
+                vec4 colour = v_colour * texture(font_texture, v_tex_coords);
+                if (!output_srgb) {
+                    colour.rgb = linear_to_srgb(colour.rgb);
+                }
+                f_color = colour;
+            }
+        ",
+    }
+}
@@ -1,10 +1,28 @@
 mod slime_moulds;
 mod app;
 mod render_pass;
+mod debug;
+mod profiler;
+mod params;
+mod scene;
+mod presets;
+mod animation;
+mod gui;
+mod batch;
+mod stats;
+mod network;
+mod benchmark;
 
 use std::time::Instant;
-use rand::{self, Rng};
+use rand::{self, Rng, SeedableRng, rngs::StdRng};
+use log::{info, LevelFilter};
 use app::{SlimeApp, handle_window_events, compute_then_render};
+use slime_moulds::{SensorKernel, RngAlgorithm, AgentGrowth};
+use scene::Scene;
+use batch::{Sweep, run_sweep};
+use benchmark::{Benchmark, run_benchmark};
+use stats::StatsLog;
+use presets::{PRESETS, find_preset};
 use winit::event_loop::EventLoop;
 use std::f32::consts::{PI, SQRT_2};
 
@@ -13,31 +31,101 @@ const HEIGHT: f32 = 1080.0;
 const WIDTH: f32 = 1920.0;
 const SCALE: f32 = 1.0;
 
-const TURN_SPEED: f32 = 0.7;
-const MOVE_SPEED: f32 = 1.0;
+// speeds and rates are per second
+const TURN_SPEED: f32 = 42.0;
+const MOVE_SPEED: f32 = 60.0;
 const SENSE_DISTANCE: f32 = 15.0;
 const SENSE_ANGLE: f32 = 73.0 * PI / 180.0;
 const SENSE_SIZE: i32 = 7;
+const SENSOR_KERNEL: SensorKernel = SensorKernel::Square;
+/// Bilinear sensing and splatted deposits, smoother at low move speeds and high SCALE.
+const SUB_PIXEL: bool = false;
 
-const DECAY_RATE: f32 = 0.002;
-const DIFFUSE_RATE: f32 = 0.05;
+const DECAY_RATE: f32 = 0.15;
+const DIFFUSE_RATE: f32 = 3.0;
+const BLUR_RADIUS: i32 = 1;
+const DEPOSIT_AMOUNT: f32 = 60.0;
+const TRAIL_COLOUR: [f32; 3] = [0.7, 0.0, 1.0];
+/// Trail colours of the agents from emitters of species 1 to 3, species 0 lays the trail colour.
+const SPECIES_COLOURS: [[f32; 3]; 3] = [[0.0, 0.9, 0.5], [1.0, 0.6, 0.0], [0.2, 0.5, 1.0]];
+
+const RNG_ALGORITHM: RngAlgorithm = RngAlgorithm::Pcg;
+
+// lifecycle, agents start with 1 energy, all zero keeps a fixed size swarm
+const MAX_AGE: f32 = 0.0;
+const ENERGY_GAIN: f32 = 0.0;
+const ENERGY_COST: f32 = 0.0;
+const SPLIT_ENERGY: f32 = 0.0;
 
 const START_CIRCLE_SIZE: f32 = 500.0;
+const NUM_AGENTS: usize = 50000;
+/// How the agent buffer makes room for agents added past its capacity.
+const AGENT_GROWTH: AgentGrowth = AgentGrowth::Double;
+
+/// Seconds to blend between presets when cycling through them.
+const PRESET_TRANSITION: f32 = 2.0;
+
+// agents per second added around the cursor while the left mouse button is held
+const SPAWN_RATE: f32 = 20000.0;
+const SPAWN_RADIUS: f32 = 30.0;
+
+/// Frames the CPU can queue up before waiting on the GPU, 2 or 3.
+const FRAMES_IN_FLIGHT: usize = 2;
 
 fn main() {
-    let mut event_loop = EventLoop::new();
-    let mut slime_agents = Vec::new();
-    let mut rng = rand::thread_rng();
-    for _ in 0..50000 {
-        let angle = rng.gen::<f32>() * PI * 2.0;
-        // let position = [(WIDTH / SCALE) * rng.gen::<f32>(), (HEIGHT / SCALE) * rng.gen::<f32>()];
-        let position = [(WIDTH / SCALE) * 0.5 + rng.gen::<f32>() * angle.cos() * START_CIRCLE_SIZE, (HEIGHT / SCALE) * 0.5 - rng.gen::<f32>() + rng.gen::<f32>() * angle.sin() * START_CIRCLE_SIZE];
-
-        slime_agents.push((position, angle))
+    let args: Vec<String> = std::env::args().collect();
+    let debug = args.iter().any(|arg| arg == "--debug");
+    init_logger(&args, debug);
+
+    // the same seed gives the same spawn and the same agent generator states
+    let seed = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--seed="))
+        .map(|seed| seed.parse().unwrap_or_else(|_| panic!("--seed expects an integer, got '{seed}'")))
+        .unwrap_or_else(|| rand::thread_rng().gen());
+    info!("seed {seed}");
+    let preset = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--preset="))
+        .map(|name| find_preset(name).unwrap_or_else(|| panic!("unknown preset '{name}'")))
+        .unwrap_or(0);
+    info!("preset {}", PRESETS[preset].name);
+
+    let mut app = SlimeApp::new(debug);
+    app.profile = args.iter().any(|arg| arg == "--profile");
+    app.seed = seed;
+    app.preset = preset;
+    app.params = PRESETS[preset].params;
+    app.async_compute = !args.iter().any(|arg| arg == "--no-async-compute");
+    app.show_gui = !args.iter().any(|arg| arg == "--no-gui");
+    if let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("--scene=")) {
+        app.scene = Scene::load(path).unwrap_or_else(|e| panic!("{e}"));
+    }
+    if let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("--stats=")) {
+        app.stats_log = Some(StatsLog::create(path).unwrap_or_else(|e| panic!("can't create {path}: {e}")));
+    }
+
+    // a sweep or benchmark runs headless and exits without opening a window. Both are read first so
+    // asking for both fails before either runs
+    let benchmark = Benchmark::from_args(&args).unwrap_or_else(|e| panic!("{e}"));
+    if let Some(sweep) = Sweep::from_args(&args).unwrap_or_else(|e| panic!("{e}")) {
+        run_sweep(&app, &sweep, PRESETS[preset].name, PRESETS[preset].spawn)
+            .unwrap_or_else(|e| panic!("sweep into {} failed: {e}", sweep.output.display()));
+        return;
+    }
+    if let Some(benchmark) = benchmark {
+        run_benchmark(&app, &benchmark, PRESETS[preset].spawn)
+            .unwrap_or_else(|e| panic!("benchmark into {} failed: {e}", benchmark.output.display()));
+        return;
     }
 
-    let mut app = SlimeApp::default();
-    app.open(&event_loop, slime_agents);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let slime_agents = PRESETS[preset].spawn.spawn(&mut rng, NUM_AGENTS, [WIDTH / SCALE, HEIGHT / SCALE]);
+
+    info!("spawned {} agents", slime_agents.len());
+
+    let mut event_loop = EventLoop::new();
+    app.open(&event_loop, slime_agents).unwrap_or_else(|e| panic!("can't start the simulation: {e}"));
 
     let mut time = Instant::now();
 
@@ -52,4 +140,28 @@ fn main() {
 }
 
 
+/// `RUST_LOG` sets the filter, `-v`/`-vv`/`-vvv` or `--log-level=<level>` on the command line
+/// override it and `-q` only shows errors.
+fn init_logger(args: &[String], debug: bool) {
+    let mut builder = env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or(if debug {"warn"} else {"error"})
+    );
 
+    let mut level = None;
+    for arg in args.iter().skip(1) {
+        match arg.as_str() {
+            "-q" => level = Some(LevelFilter::Error),
+            "-v" => level = Some(LevelFilter::Info),
+            "-vv" => level = Some(LevelFilter::Debug),
+            "-vvv" => level = Some(LevelFilter::Trace),
+            _ => if let Some(name) = arg.strip_prefix("--log-level=") {
+                level = Some(name.parse().unwrap_or_else(|_| panic!("unknown log level '{name}'")));
+            }
+        }
+    }
+    if let Some(level) = level {
+        builder.filter_level(level);
+    }
+
+    builder.init();
+}
@@ -0,0 +1,575 @@
+use std::f32::consts::SQRT_2;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+
+/// Trail strength a pixel needs to be part of the network unless told otherwise.
+pub const NETWORK_THRESHOLD: f32 = 0.2;
+
+/// The 8 neighbours of a pixel clockwise from north, thinning relies on the order.
+const NEIGHBOURS: [(isize, isize); 8] = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+
+const NO_NODE: usize = usize::MAX;
+
+
+/// A junction, a dead end, or a point on an otherwise unbroken loop.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Node {
+    /// Simulation pixels, the middle of the skeleton pixels making up the node.
+    pub pos: [f32; 2],
+    /// Edges meeting at the node, a loop back to it counts twice.
+    pub degree: u32,
+}
+
+
+/// A stretch of skeleton between two nodes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    /// Along the skeleton in simulation pixels.
+    pub length: f32,
+    /// Average width of the thresholded trail around the skeleton, in simulation pixels.
+    pub width: f32,
+}
+
+
+/// The network of trails as a graph, from thresholding the trail strength and thinning what's left
+/// down to lines one pixel wide.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Network {
+    pub size: [u32; 2],
+    pub threshold: f32,
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl Network {
+    /// Extracts the network from the trail strength of each pixel, row by row, counting pixels at
+    /// least `threshold` strong as trail.
+    pub fn extract(strength: &[f32], size: [u32; 2], threshold: f32) -> Network {
+        let width = size[0] as usize;
+        let height = size[1] as usize;
+        assert_eq!(strength.len(), width * height, "expected a strength for each of the {}x{} pixels", width, height);
+
+        // the border stays empty so every trail pixel has all 8 neighbours
+        let mut mask: Vec<bool> = strength.iter().map(|&s| s >= threshold).collect();
+        for x in 0..width {
+            mask[x] = false;
+            mask[(height - 1) * width + x] = false;
+        }
+        for y in 0..height {
+            mask[y * width] = false;
+            mask[y * width + width - 1] = false;
+        }
+
+        let distance = distance_to_background(&mask, width);
+        thin(&mut mask, width);
+        remove_staircases(&mut mask, width);
+
+        let mut network = trace(&mask, &distance, width);
+        network.size = size;
+        network.threshold = threshold;
+        network.merge_pass_through_nodes();
+        network
+    }
+
+    pub fn total_length(&self) -> f32 {
+        self.edges.iter().map(|edge| edge.length).sum()
+    }
+
+    /// How many nodes there are of each degree, indexed by degree.
+    pub fn degree_distribution(&self) -> Vec<u32> {
+        let max_degree = self.nodes.iter().map(|node| node.degree).max().unwrap_or(0);
+        let mut counts = vec![0; max_degree as usize + 1];
+        for node in &self.nodes {
+            counts[node.degree as usize] += 1;
+        }
+        counts
+    }
+
+    /// Separate pieces of the network.
+    pub fn components(&self) -> usize {
+        let mut pieces = Pieces::new(self.nodes.len());
+        let mut components = self.nodes.len();
+        for edge in &self.edges {
+            if pieces.join(edge.from, edge.to) {
+                components -= 1;
+            }
+        }
+        components
+    }
+
+    /// Independent loops in the network, the edges left over once each piece is a tree.
+    pub fn cycles(&self) -> usize {
+        self.edges.len() + self.components() - self.nodes.len()
+    }
+
+    /// Writes `<path>.graphml` and `<path>.json`.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        fs::write(path.with_extension("graphml"), self.to_graphml())?;
+        fs::write(path.with_extension("json"), self.to_json())
+    }
+
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"x\" for=\"node\" attr.name=\"x\" attr.type=\"float\"/>\n",
+            "  <key id=\"y\" for=\"node\" attr.name=\"y\" attr.type=\"float\"/>\n",
+            "  <key id=\"degree\" for=\"node\" attr.name=\"degree\" attr.type=\"int\"/>\n",
+            "  <key id=\"length\" for=\"edge\" attr.name=\"length\" attr.type=\"float\"/>\n",
+            "  <key id=\"width\" for=\"edge\" attr.name=\"width\" attr.type=\"float\"/>\n",
+            "  <graph id=\"network\" edgedefault=\"undirected\">\n",
+        ));
+        for (i, node) in self.nodes.iter().enumerate() {
+            writeln!(
+                xml,
+                "    <node id=\"n{i}\"><data key=\"x\">{}</data><data key=\"y\">{}</data><data key=\"degree\">{}</data></node>",
+                node.pos[0], node.pos[1], node.degree
+            )
+            .unwrap();
+        }
+        for (i, edge) in self.edges.iter().enumerate() {
+            writeln!(
+                xml,
+                "    <edge id=\"e{i}\" source=\"n{}\" target=\"n{}\"><data key=\"length\">{}</data><data key=\"width\">{}</data></edge>",
+                edge.from, edge.to, edge.length, edge.width
+            )
+            .unwrap();
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+
+    pub fn to_json(&self) -> String {
+        let degrees = self.degree_distribution().iter().map(u32::to_string).collect::<Vec<_>>().join(", ");
+        let mut json = String::from("{\n");
+        writeln!(json, "  \"width\": {},", self.size[0]).unwrap();
+        writeln!(json, "  \"height\": {},", self.size[1]).unwrap();
+        writeln!(json, "  \"threshold\": {},", self.threshold).unwrap();
+        writeln!(
+            json,
+            "  \"summary\": {{\"nodes\": {}, \"edges\": {}, \"total_length\": {}, \"components\": {}, \"cycles\": {}, \"degrees\": [{degrees}]}},",
+            self.nodes.len(), self.edges.len(), self.total_length(), self.components(), self.cycles()
+        )
+        .unwrap();
+
+        json.push_str("  \"nodes\": [\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let comma = if i + 1 < self.nodes.len() {","} else {""};
+            writeln!(json, "    {{\"x\": {}, \"y\": {}, \"degree\": {}}}{comma}", node.pos[0], node.pos[1], node.degree).unwrap();
+        }
+        json.push_str("  ],\n  \"edges\": [\n");
+        for (i, edge) in self.edges.iter().enumerate() {
+            let comma = if i + 1 < self.edges.len() {","} else {""};
+            writeln!(
+                json,
+                "    {{\"source\": {}, \"target\": {}, \"length\": {}, \"width\": {}}}{comma}",
+                edge.from, edge.to, edge.length, edge.width
+            )
+            .unwrap();
+        }
+        json.push_str("  ]\n}\n");
+        json
+    }
+
+    /// Joins the two edges either side of every node with degree 2 into one, these are left where
+    /// the skeleton is a little thick rather than at real junctions. Nodes alone on a loop stay, and
+    /// specks without any edges are dropped.
+    fn merge_pass_through_nodes(&mut self) {
+        let mut node_edges = vec![Vec::new(); self.nodes.len()];
+        for (i, edge) in self.edges.iter().enumerate() {
+            node_edges[edge.from].push(i);
+            node_edges[edge.to].push(i);
+        }
+        let mut edges: Vec<Option<Edge>> = self.edges.drain(..).map(Some).collect();
+
+        let mut removed: Vec<bool> = node_edges.iter().map(Vec::is_empty).collect();
+        for node in 0..self.nodes.len() {
+            let [first, second] = node_edges[node][..] else {
+                continue;
+            };
+            if first == second {
+                continue;
+            }
+            let (a, b) = (edges[first].unwrap(), edges[second].take().unwrap());
+            let other_end = |edge: Edge| if edge.from == node { edge.to } else { edge.from };
+            let (start, end) = (other_end(a), other_end(b));
+            let length = a.length + b.length;
+            let width = if length > 0.0 { (a.width * a.length + b.width * b.length) / length } else { a.width };
+            edges[first] = Some(Edge { from: start, to: end, length, width });
+
+            for index in node_edges[end].iter_mut() {
+                if *index == second {
+                    *index = first;
+                }
+            }
+            node_edges[node].clear();
+            removed[node] = true;
+        }
+
+        let mut new_index = vec![NO_NODE; self.nodes.len()];
+        let mut nodes = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if !removed[i] {
+                new_index[i] = nodes.len();
+                nodes.push(Node { degree: 0, ..*node });
+            }
+        }
+        self.edges = edges
+            .into_iter()
+            .flatten()
+            .map(|edge| Edge { from: new_index[edge.from], to: new_index[edge.to], ..edge })
+            .collect();
+        for edge in &self.edges {
+            nodes[edge.from].degree += 1;
+            nodes[edge.to].degree += 1;
+        }
+        self.nodes = nodes;
+    }
+}
+
+
+/// Which nodes are joined up by the edges added so far, as a union-find.
+pub struct Pieces {
+    parents: Vec<usize>,
+}
+
+impl Pieces {
+    /// `len` nodes, each a piece of its own.
+    pub fn new(len: usize) -> Self {
+        Pieces { parents: (0..len).collect() }
+    }
+
+    /// The node standing for the piece `node` is in, the same for every node in it.
+    pub fn root(&mut self, mut node: usize) -> usize {
+        while self.parents[node] != node {
+            self.parents[node] = self.parents[self.parents[node]];
+            node = self.parents[node];
+        }
+        node
+    }
+
+    /// Joins the pieces `a` and `b` are in, false if they were already one piece.
+    pub fn join(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.root(a), self.root(b));
+        self.parents[a] = b;
+        a != b
+    }
+}
+
+
+fn neighbour(pixel: usize, width: usize, direction: usize) -> usize {
+    let (dx, dy) = NEIGHBOURS[direction];
+    (pixel as isize + dy * width as isize + dx) as usize
+}
+
+fn step_length(direction: usize) -> f32 {
+    [1.0, SQRT_2][direction % 2]
+}
+
+fn ring(mask: &[bool], pixel: usize, width: usize) -> [bool; 8] {
+    std::array::from_fn(|direction| mask[neighbour(pixel, width, direction)])
+}
+
+
+/// Chamfer distance from each trail pixel to the nearest pixel outside the trail.
+fn distance_to_background(mask: &[bool], width: usize) -> Vec<f32> {
+    let mut distance: Vec<f32> = mask.iter().map(|&trail| if trail { f32::MAX } else { 0.0 }).collect();
+    let relax = |distance: &mut [f32], pixel: usize, directions: [usize; 4]| {
+        if mask[pixel] {
+            for direction in directions {
+                let through = distance[neighbour(pixel, width, direction)] + step_length(direction);
+                distance[pixel] = distance[pixel].min(through);
+            }
+        }
+    };
+    // through the neighbours before each pixel in reading order, then the ones after going backwards
+    for pixel in 0..mask.len() {
+        relax(&mut distance, pixel, [6, 7, 0, 1]);
+    }
+    for pixel in (0..mask.len()).rev() {
+        relax(&mut distance, pixel, [2, 3, 4, 5]);
+    }
+    distance
+}
+
+
+/// Zhang-Suen thinning, peels pixels off the outside of the trail until only lines one pixel wide
+/// are left, without breaking any apart.
+fn thin(mask: &mut [bool], width: usize) {
+    let mut foreground: Vec<usize> = (0..mask.len()).filter(|&pixel| mask[pixel]).collect();
+    loop {
+        let mut changed = false;
+        for pass in 0..2 {
+            let remove: Vec<usize> = foreground
+                .iter()
+                .copied()
+                .filter(|&pixel| {
+                    let p = ring(mask, pixel, width);
+                    let [n, _, e, _, s, _, w, _] = p;
+                    let count = p.iter().filter(|&&set| set).count();
+                    let transitions = (0..8).filter(|&i| !p[i] && p[(i + 1) % 8]).count();
+                    let keep = if pass == 0 { e && s && (n || w) } else { n && w && (e || s) };
+                    (2..=6).contains(&count) && transitions == 1 && !keep
+                })
+                .collect();
+            for &pixel in &remove {
+                mask[pixel] = false;
+            }
+            changed |= !remove.is_empty();
+            foreground.retain(|&pixel| mask[pixel]);
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+
+/// Thinning leaves corners on diagonal lines where a pixel joins two neighbours that already touch,
+/// which would otherwise look like junctions.
+fn remove_staircases(mask: &mut [bool], width: usize) {
+    for pixel in 0..mask.len() {
+        if !mask[pixel] {
+            continue;
+        }
+        let p = ring(mask, pixel, width);
+        let redundant = (0..4).any(|side| {
+            let a = side * 2;
+            p[a] && p[(a + 2) % 8] && !p[(a + 4) % 8] && !p[(a + 5) % 8] && !p[(a + 6) % 8]
+        });
+        if redundant {
+            mask[pixel] = false;
+        }
+    }
+}
+
+
+/// Follows the skeleton into nodes, where it ends or branches, and the edges between them.
+fn trace(skeleton: &[bool], distance: &[f32], width: usize) -> Network {
+    let neighbour_count = |pixel: usize| ring(skeleton, pixel, width).iter().filter(|&&set| set).count();
+    // a line is as wide as twice the distance to its edge, less the middle pixel counted twice
+    let line_width = |pixel: usize| (distance[pixel] * 2.0 - 1.0).max(1.0);
+
+    // neighbouring pixels that aren't part of a line make up one node
+    let mut node_of = vec![NO_NODE; skeleton.len()];
+    let mut members: Vec<Vec<usize>> = Vec::new();
+    for pixel in 0..skeleton.len() {
+        if !skeleton[pixel] || node_of[pixel] != NO_NODE || neighbour_count(pixel) == 2 {
+            continue;
+        }
+        let node = members.len();
+        let mut group = Vec::new();
+        let mut stack = vec![pixel];
+        node_of[pixel] = node;
+        while let Some(current) = stack.pop() {
+            group.push(current);
+            for direction in 0..8 {
+                let next = neighbour(current, width, direction);
+                if skeleton[next] && node_of[next] == NO_NODE && neighbour_count(next) != 2 {
+                    node_of[next] = node;
+                    stack.push(next);
+                }
+            }
+        }
+        members.push(group);
+    }
+
+    let mut edges = Vec::new();
+    let mut visited = vec![false; skeleton.len()];
+    // walks from a node pixel along a line until it reaches a node, returning the edge to it
+    let follow = |start: usize, direction: usize, node_of: &[usize], visited: &mut [bool]| -> Option<Edge> {
+        let mut previous = start;
+        let mut current = neighbour(start, width, direction);
+        let mut length = step_length(direction);
+        let mut widths = 0.0;
+        let mut pixels = 0;
+        loop {
+            visited[current] = true;
+            widths += line_width(current);
+            pixels += 1;
+            let direction = (0..8).find(|&direction| {
+                let next = neighbour(current, width, direction);
+                skeleton[next] && next != previous
+            })?;
+            let next = neighbour(current, width, direction);
+            length += step_length(direction);
+            if node_of[next] != NO_NODE {
+                // cutting the corner between two pixels of the same junction isn't an edge
+                if node_of[next] == node_of[start] && pixels < 3 {
+                    return None;
+                }
+                return Some(Edge { from: node_of[start], to: node_of[next], length, width: widths / pixels as f32 });
+            }
+            if visited[next] {
+                return None;
+            }
+            previous = current;
+            current = next;
+        }
+    };
+
+    // node pixels next to each other are in the same node, so every edge starts off along a line
+    for &pixel in members.iter().flatten() {
+        for direction in 0..8 {
+            let next = neighbour(pixel, width, direction);
+            if skeleton[next] && node_of[next] == NO_NODE && !visited[next] {
+                edges.extend(follow(pixel, direction, &node_of, &mut visited));
+            }
+        }
+    }
+
+    // whatever's left are loops without any junctions, each gets a node to start and end at
+    for pixel in 0..skeleton.len() {
+        if !skeleton[pixel] || node_of[pixel] != NO_NODE || visited[pixel] {
+            continue;
+        }
+        node_of[pixel] = members.len();
+        members.push(vec![pixel]);
+        visited[pixel] = true;
+        if let Some(direction) = (0..8).find(|&direction| skeleton[neighbour(pixel, width, direction)]) {
+            edges.extend(follow(pixel, direction, &node_of, &mut visited));
+        }
+    }
+
+    let nodes = members
+        .iter()
+        .map(|group| {
+            let sum = group.iter().fold([0.0, 0.0], |[x, y], &pixel| {
+                [x + (pixel % width) as f32, y + (pixel / width) as f32]
+            });
+            Node { pos: [sum[0] / group.len() as f32, sum[1] / group.len() as f32], degree: 0 }
+        })
+        .collect();
+    Network { nodes, edges, ..Default::default() }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// Full strength wherever `trail` is true on a `size` pixel image.
+    fn strength(size: [u32; 2], trail: impl Fn(f32, f32) -> bool) -> Vec<f32> {
+        (0..size[1])
+            .flat_map(|y| (0..size[0]).map(move |x| (x as f32, y as f32)))
+            .map(|(x, y)| if trail(x, y) { 1.0 } else { 0.0 })
+            .collect()
+    }
+
+    fn mask(size: [u32; 2], pixels: &[(usize, usize)]) -> Vec<bool> {
+        let mut mask = vec![false; (size[0] * size[1]) as usize];
+        for &(x, y) in pixels {
+            mask[y * size[0] as usize + x] = true;
+        }
+        mask
+    }
+
+    fn in_ring(x: f32, y: f32, centre: [f32; 2]) -> bool {
+        let radius = ((x - centre[0]).powi(2) + (y - centre[1]).powi(2)).sqrt();
+        (10.0..=13.0).contains(&radius)
+    }
+
+
+    #[test]
+    fn thinning_leaves_a_line_one_pixel_wide() {
+        let size = [20, 9];
+        let width = size[0] as usize;
+        let mut mask = mask(size, &(2..18).flat_map(|x| (2..7).map(move |y| (x, y))).collect::<Vec<_>>());
+        thin(&mut mask, width);
+
+        let set: Vec<usize> = (0..mask.len()).filter(|&pixel| mask[pixel]).collect();
+        assert!(!set.is_empty());
+        for &pixel in &set {
+            let neighbours = ring(&mask, pixel, width).iter().filter(|&&set| set).count();
+            assert!((1..=2).contains(&neighbours), "pixel {pixel} has {neighbours} neighbours");
+        }
+        // every column the bar covered away from its ends still has exactly one pixel
+        for x in 5..14 {
+            assert_eq!((0..size[1] as usize).filter(|&y| mask[y * width + x]).count(), 1, "column {x}");
+        }
+    }
+
+    #[test]
+    fn staircase_corners_are_removed() {
+        let size = [7, 7];
+        let mut skeleton = mask(size, &[(1, 1), (2, 1), (2, 2), (3, 2), (3, 3)]);
+        remove_staircases(&mut skeleton, size[0] as usize);
+        assert_eq!(skeleton, mask(size, &[(1, 1), (2, 2), (3, 3)]));
+    }
+
+    #[test]
+    fn trace_follows_a_line_between_its_ends() {
+        let size = [12, 7];
+        let mask = mask(size, &(2..=8).map(|x| (x, 3)).collect::<Vec<_>>());
+        let network = trace(&mask, &vec![1.0; mask.len()], size[0] as usize);
+        assert_eq!(network.nodes.len(), 2);
+        assert_eq!(network.edges.len(), 1);
+        assert_eq!(network.edges[0].length, 6.0);
+        assert_eq!(network.edges[0].width, 1.0);
+        let mut ends: Vec<[f32; 2]> = network.nodes.iter().map(|node| node.pos).collect();
+        ends.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        assert_eq!(ends, [[2.0, 3.0], [8.0, 3.0]]);
+    }
+
+    #[test]
+    fn pass_through_nodes_are_merged() {
+        let node = |x| Node { pos: [x, 0.0], degree: 0 };
+        let mut network = Network {
+            nodes: vec![node(0.0), node(10.0), node(30.0), node(50.0)],
+            edges: vec![
+                Edge { from: 0, to: 1, length: 10.0, width: 1.0 },
+                Edge { from: 1, to: 2, length: 30.0, width: 3.0 },
+            ],
+            ..Default::default()
+        };
+        network.merge_pass_through_nodes();
+        // the speck at 50 goes too
+        assert_eq!(network.nodes, [Node { pos: [0.0, 0.0], degree: 1 }, Node { pos: [30.0, 0.0], degree: 1 }]);
+        assert_eq!(network.edges, [Edge { from: 0, to: 1, length: 40.0, width: 2.5 }]);
+    }
+
+    #[test]
+    fn plus_sign_is_one_junction_and_four_edges() {
+        let size = [41, 41];
+        let strength = strength(size, |x, y| {
+            let across = (18.0..=22.0).contains(&y) && (5.0..=35.0).contains(&x);
+            let down = (18.0..=22.0).contains(&x) && (5.0..=35.0).contains(&y);
+            across || down
+        });
+        let network = Network::extract(&strength, size, NETWORK_THRESHOLD);
+        assert_eq!(network.edges.len(), 4);
+        assert_eq!(network.degree_distribution(), [0, 4, 0, 0, 1]);
+        assert_eq!(network.components(), 1);
+        assert_eq!(network.cycles(), 0);
+    }
+
+    #[test]
+    fn ring_is_one_node_and_one_cycle() {
+        let size = [41, 41];
+        let strength = strength(size, |x, y| in_ring(x, y, [20.0, 20.0]));
+        let network = Network::extract(&strength, size, NETWORK_THRESHOLD);
+        assert_eq!(network.nodes.len(), 1);
+        assert_eq!(network.nodes[0].degree, 2);
+        assert_eq!(network.edges.len(), 1);
+        assert_eq!(network.cycles(), 1);
+        // roughly the circumference of the middle of the ring
+        let length = network.total_length();
+        assert!((65.0..80.0).contains(&length), "ring is {length} long");
+    }
+
+    #[test]
+    fn figure_eight_is_two_cycles() {
+        let size = [64, 41];
+        let strength = strength(size, |x, y| in_ring(x, y, [20.0, 20.0]) || in_ring(x, y, [43.0, 20.0]));
+        let network = Network::extract(&strength, size, NETWORK_THRESHOLD);
+        assert_eq!(network.components(), 1);
+        assert_eq!(network.cycles(), 2);
+    }
+}
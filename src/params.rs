@@ -0,0 +1,102 @@
+use crate::slime_moulds::{SensorKernel, RngAlgorithm};
+use crate::{TURN_SPEED, MOVE_SPEED, SENSE_DISTANCE, SENSE_ANGLE, SENSE_SIZE, SENSOR_KERNEL, SUB_PIXEL, DECAY_RATE, DIFFUSE_RATE, BLUR_RADIUS, DEPOSIT_AMOUNT, TRAIL_COLOUR, RNG_ALGORITHM, MAX_AGE, ENERGY_GAIN, ENERGY_COST, SPLIT_ENERGY};
+
+
+/// Everything that shapes how the agents move and the trail evolves.
+///
+/// Speeds and rates are per second. The defaults are the constants in `main.rs`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SimParams {
+    pub turn_speed: f32,
+    pub move_speed: f32,
+    pub sense_distance: f32,
+    /// Radians between the forward sensor and each side sensor.
+    pub sensor_angle: f32,
+    /// Half width of the area each sensor sums over.
+    pub sensor_size: i32,
+    pub sensor_kernel: SensorKernel,
+    /// Bilinear sensing and splatted deposits.
+    pub sub_pixel: bool,
+    pub decay_rate: f32,
+    pub diffuse_rate: f32,
+    pub blur_radius: i32,
+    pub deposit_amount: f32,
+    /// Linear RGB the trail is drawn in, it doesn't change what the agents sense.
+    pub trail_colour: [f32; 3],
+    pub rng_algorithm: RngAlgorithm,
+    /// Seconds an agent lives for, 0 for forever.
+    pub max_age: f32,
+    /// Energy gained per second per unit of trail under the agent.
+    pub energy_gain: f32,
+    /// Energy spent per second, agents starve when they run out.
+    pub energy_cost: f32,
+    /// Energy at which an agent splits in two, 0 to never split.
+    pub split_energy: f32,
+}
+
+impl SimParams {
+    /// The constants in `main.rs`.
+    pub const DEFAULT: SimParams = SimParams {
+        turn_speed: TURN_SPEED,
+        move_speed: MOVE_SPEED,
+        sense_distance: SENSE_DISTANCE,
+        sensor_angle: SENSE_ANGLE,
+        sensor_size: SENSE_SIZE,
+        sensor_kernel: SENSOR_KERNEL,
+        sub_pixel: SUB_PIXEL,
+        decay_rate: DECAY_RATE,
+        diffuse_rate: DIFFUSE_RATE,
+        blur_radius: BLUR_RADIUS,
+        deposit_amount: DEPOSIT_AMOUNT,
+        trail_colour: TRAIL_COLOUR,
+        rng_algorithm: RNG_ALGORITHM,
+        max_age: MAX_AGE,
+        energy_gain: ENERGY_GAIN,
+        energy_cost: ENERGY_COST,
+        split_energy: SPLIT_ENERGY,
+    };
+
+    /// Blends towards `other` as `t` goes from 0 to 1. Values baked into the pipelines switch
+    /// over at the halfway point, so a blend only rebuilds them once, as do the lifespan and split
+    /// energy since blending from 0 (off) would pass through tiny values.
+    pub fn lerp(&self, other: &SimParams, t: f32) -> SimParams {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        let discrete = if t < 0.5 { self } else { other };
+        SimParams {
+            turn_speed: mix(self.turn_speed, other.turn_speed),
+            move_speed: mix(self.move_speed, other.move_speed),
+            sense_distance: mix(self.sense_distance, other.sense_distance),
+            sensor_angle: mix(self.sensor_angle, other.sensor_angle),
+            sensor_size: discrete.sensor_size,
+            sensor_kernel: discrete.sensor_kernel,
+            sub_pixel: discrete.sub_pixel,
+            decay_rate: mix(self.decay_rate, other.decay_rate),
+            diffuse_rate: mix(self.diffuse_rate, other.diffuse_rate),
+            blur_radius: discrete.blur_radius,
+            deposit_amount: mix(self.deposit_amount, other.deposit_amount),
+            trail_colour: [0, 1, 2].map(|i| mix(self.trail_colour[i], other.trail_colour[i])),
+            rng_algorithm: discrete.rng_algorithm,
+            max_age: discrete.max_age,
+            energy_gain: mix(self.energy_gain, other.energy_gain),
+            energy_cost: mix(self.energy_cost, other.energy_cost),
+            split_energy: discrete.split_energy,
+        }
+    }
+
+    /// True if the two differ in a value baked into the compute pipelines as a specialisation
+    /// constant, so switching between them means rebuilding the pipelines rather than just
+    /// rewriting the settings buffer.
+    pub fn needs_rebuild(&self, other: &SimParams) -> bool {
+        self.sensor_size != other.sensor_size
+            || self.sensor_kernel != other.sensor_kernel
+            || self.sub_pixel != other.sub_pixel
+            || self.blur_radius != other.blur_radius
+            || self.rng_algorithm != other.rng_algorithm
+    }
+}
+
+impl Default for SimParams {
+    fn default() -> Self {
+        SimParams::DEFAULT
+    }
+}
@@ -0,0 +1,176 @@
+use std::f32::consts::PI;
+use rand::{rngs::StdRng, Rng};
+use crate::params::SimParams;
+use crate::slime_moulds::{SensorKernel, RngAlgorithm};
+use crate::START_CIRCLE_SIZE;
+
+
+/// Where the starting agents are placed and which way they face.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpawnMode {
+    /// Scattered over a disc in the middle, facing away from the centre.
+    Disc { radius: f32 },
+    /// Anywhere in the simulation, facing any direction.
+    Uniform,
+    /// On a circle in the middle, facing inwards.
+    Ring { radius: f32 },
+}
+
+impl SpawnMode {
+    /// `count` agents for a simulation of `size` pixels.
+    pub fn spawn(&self, rng: &mut StdRng, count: usize, size: [f32; 2]) -> Vec<([f32; 2], f32)> {
+        let centre = [size[0] * 0.5, size[1] * 0.5];
+        (0..count)
+            .map(|_| match *self {
+                SpawnMode::Disc { radius } => {
+                    let angle = rng.gen::<f32>() * PI * 2.0;
+                    let position = [centre[0] + rng.gen::<f32>() * angle.cos() * radius, centre[1] - rng.gen::<f32>() + rng.gen::<f32>() * angle.sin() * radius];
+                    (position, angle)
+                }
+                SpawnMode::Uniform => {
+                    let position = [size[0] * rng.gen::<f32>(), size[1] * rng.gen::<f32>()];
+                    (position, rng.gen::<f32>() * PI * 2.0)
+                }
+                SpawnMode::Ring { radius } => {
+                    let theta = rng.gen::<f32>() * PI * 2.0;
+                    let position = [centre[0] + theta.cos() * radius, centre[1] + theta.sin() * radius];
+                    (position, theta + PI)
+                }
+            })
+            .collect()
+    }
+}
+
+
+/// A named set of parameters along with how to spawn the swarm for them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Preset {
+    pub name: &'static str,
+    pub params: SimParams,
+    pub spawn: SpawnMode,
+}
+
+/// Built in presets, the first is the constants in `main.rs`.
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "default",
+        params: SimParams::DEFAULT,
+        spawn: SpawnMode::Disc { radius: START_CIRCLE_SIZE },
+    },
+    // short sensors and quick decay keep the cells small and packed together
+    Preset {
+        name: "dense network",
+        params: SimParams {
+            turn_speed: 30.0,
+            move_speed: 45.0,
+            sense_distance: 9.0,
+            sensor_angle: 45.0 * PI / 180.0,
+            sensor_size: 3,
+            sensor_kernel: SensorKernel::Square,
+            sub_pixel: false,
+            decay_rate: 0.4,
+            diffuse_rate: 5.0,
+            blur_radius: 1,
+            deposit_amount: 40.0,
+            trail_colour: [0.2, 0.9, 0.5],
+            rng_algorithm: RngAlgorithm::Pcg,
+            ..SimParams::DEFAULT
+        },
+        spawn: SpawnMode::Uniform,
+    },
+    // long narrow sensors and little diffusion draw fine trails over long distances
+    Preset {
+        name: "thin veins",
+        params: SimParams {
+            turn_speed: 20.0,
+            move_speed: 80.0,
+            sense_distance: 25.0,
+            sensor_angle: 22.5 * PI / 180.0,
+            sensor_size: 1,
+            sensor_kernel: SensorKernel::Gaussian,
+            sub_pixel: true,
+            decay_rate: 0.6,
+            diffuse_rate: 1.0,
+            blur_radius: 1,
+            deposit_amount: 30.0,
+            trail_colour: [1.0, 0.8, 0.3],
+            rng_algorithm: RngAlgorithm::Pcg,
+            ..SimParams::DEFAULT
+        },
+        spawn: SpawnMode::Uniform,
+    },
+    // sensors wider than the turn curl the agents round into rotating cells
+    Preset {
+        name: "spiral cells",
+        params: SimParams {
+            turn_speed: 90.0,
+            move_speed: 50.0,
+            sense_distance: 30.0,
+            sensor_angle: 100.0 * PI / 180.0,
+            sensor_size: 2,
+            sensor_kernel: SensorKernel::Circle,
+            sub_pixel: false,
+            decay_rate: 0.1,
+            diffuse_rate: 2.0,
+            blur_radius: 1,
+            deposit_amount: 80.0,
+            trail_colour: [0.3, 0.6, 1.0],
+            rng_algorithm: RngAlgorithm::Pcg,
+            ..SimParams::DEFAULT
+        },
+        spawn: SpawnMode::Ring { radius: 400.0 },
+    },
+    // fast agents over a heavily blurred trail never settle into a network
+    Preset {
+        name: "turbulent",
+        params: SimParams {
+            turn_speed: 120.0,
+            move_speed: 120.0,
+            sense_distance: 12.0,
+            sensor_angle: 60.0 * PI / 180.0,
+            sensor_size: 5,
+            sensor_kernel: SensorKernel::Square,
+            sub_pixel: false,
+            decay_rate: 0.25,
+            diffuse_rate: 8.0,
+            blur_radius: 2,
+            deposit_amount: 60.0,
+            trail_colour: [1.0, 0.3, 0.2],
+            rng_algorithm: RngAlgorithm::Pcg,
+            ..SimParams::DEFAULT
+        },
+        spawn: SpawnMode::Disc { radius: 200.0 },
+    },
+];
+
+/// Looks a preset up by name, ignoring case.
+pub fn find_preset(name: &str) -> Option<usize> {
+    PRESETS.iter().position(|preset| preset.name.eq_ignore_ascii_case(name))
+}
+
+
+/// Blends the simulation parameters from one preset's over to another's.
+pub struct PresetTransition {
+    from: SimParams,
+    to: SimParams,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl PresetTransition {
+    pub fn new(from: SimParams, to: SimParams, duration: f32) -> Self {
+        PresetTransition { from, to, elapsed: 0.0, duration }
+    }
+
+    /// Moves the blend on by `delta_time` seconds and returns the parameters to use, eased in and
+    /// out so the swarm doesn't jolt at either end.
+    pub fn advance(&mut self, delta_time: f32) -> SimParams {
+        self.elapsed += delta_time;
+        let t = if self.duration > 0.0 { (self.elapsed / self.duration).min(1.0) } else { 1.0 };
+        self.from.lerp(&self.to, t * t * (3.0 - 2.0 * t))
+    }
+
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
@@ -0,0 +1,151 @@
+use std::{collections::VecDeque, sync::Arc};
+use log::info;
+use vulkano::{
+    command_buffer::{allocator::StandardCommandBufferAllocator, AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    device::Queue,
+    query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType},
+    sync::PipelineStage,
+};
+
+
+/// Number of frames the rolling averages are taken over.
+const HISTORY_LEN: usize = 60;
+
+
+/// Brackets a fixed list of GPU passes with timestamp queries and keeps a rolling average of how
+/// long each one took.
+///
+/// Call [`GpuTimer::reset`] once at the start of the command buffer, then [`GpuTimer::begin`] and
+/// [`GpuTimer::end`] around each pass.
+///
+/// There is a query pool per frame in flight, so a frame's queries are only reused once the app
+/// has waited for that frame to finish.
+pub struct GpuTimer {
+    pools: Vec<Arc<QueryPool>>,
+    /// Frame slot the queries are currently being written for.
+    frame: usize,
+    passes: Vec<&'static str>,
+    history: Vec<VecDeque<f32>>,
+    period_ms: f64,
+    valid_mask: u64,
+    written: Vec<bool>,
+}
+
+impl GpuTimer {
+    /// Returns `None` if the queue family can't write timestamps.
+    pub fn new(queue: &Arc<Queue>, passes: &[&'static str], frames_in_flight: usize) -> Option<GpuTimer> {
+        let device = queue.device();
+        let physical = device.physical_device();
+        let valid_bits = physical.queue_family_properties()[queue.queue_family_index() as usize]
+            .timestamp_valid_bits?;
+
+        let pools = (0..frames_in_flight)
+            .map(|_| {
+                QueryPool::new(
+                    device.clone(),
+                    QueryPoolCreateInfo {
+                        query_count: passes.len() as u32 * 2,
+                        ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+                    },
+                )
+                .unwrap()
+            })
+            .collect();
+
+        Some(GpuTimer {
+            pools,
+            frame: 0,
+            passes: passes.to_vec(),
+            history: vec![VecDeque::with_capacity(HISTORY_LEN); passes.len()],
+            period_ms: physical.properties().timestamp_period as f64 / 1_000_000.0,
+            valid_mask: if valid_bits >= 64 { u64::MAX } else { (1 << valid_bits) - 1 },
+            written: vec![false; frames_in_flight],
+        })
+    }
+
+    /// Collects the results of the last submission in this frame slot if the GPU has finished
+    /// with them, then resets the slot's queries for this one.
+    pub fn reset(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer, Arc<StandardCommandBufferAllocator>>,
+        frame: usize,
+    ) {
+        self.frame = frame;
+        if self.written[frame] {
+            self.collect();
+        }
+
+        // the queries are only written after this reset in the same command buffer
+        let pool = &self.pools[frame];
+        unsafe {
+            builder.reset_query_pool(pool.clone(), 0..pool.query_count()).unwrap();
+        }
+        self.written[frame] = true;
+    }
+
+    pub fn begin(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer, Arc<StandardCommandBufferAllocator>>,
+        pass: usize,
+    ) {
+        unsafe {
+            builder.write_timestamp(self.pools[self.frame].clone(), pass as u32 * 2, PipelineStage::TopOfPipe).unwrap();
+        }
+    }
+
+    pub fn end(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer, Arc<StandardCommandBufferAllocator>>,
+        pass: usize,
+    ) {
+        unsafe {
+            builder.write_timestamp(self.pools[self.frame].clone(), pass as u32 * 2 + 1, PipelineStage::BottomOfPipe).unwrap();
+        }
+    }
+
+    fn collect(&mut self) {
+        let mut results = vec![0u64; self.passes.len() * 2];
+        let pool = &self.pools[self.frame];
+        let ready = pool
+            .queries_range(0..pool.query_count())
+            .unwrap()
+            .get_results(&mut results, QueryResultFlags::empty())
+            .unwrap();
+        if !ready {
+            return;
+        }
+
+        for (pass, history) in self.history.iter_mut().enumerate() {
+            let ticks = results[pass * 2 + 1].wrapping_sub(results[pass * 2]) & self.valid_mask;
+            if history.len() == HISTORY_LEN {
+                history.pop_front();
+            }
+            history.push_back((ticks as f64 * self.period_ms) as f32);
+        }
+    }
+
+    /// Average time of each pass in milliseconds over the last few frames.
+    pub fn averages(&self) -> Vec<(&'static str, f32)> {
+        self.passes
+            .iter()
+            .zip(&self.history)
+            .map(|(name, history)| {
+                let average = if history.is_empty() {
+                    0.0
+                } else {
+                    history.iter().sum::<f32>() / history.len() as f32
+                };
+                (*name, average)
+            })
+            .collect()
+    }
+}
+
+
+pub fn log_timings(timings: &[(&'static str, f32)]) {
+    let summary: Vec<String> = timings
+        .iter()
+        .map(|(name, ms)| format!("{name} {ms:.3}ms"))
+        .collect();
+    info!("gpu {}", summary.join(", "));
+}
@@ -1,4 +1,5 @@
 use std::sync::Arc;
+use log::debug;
 use vulkano::{
     buffer::{BufferContents, Buffer, BufferCreateInfo, BufferUsage, Subbuffer},
     pipeline::{
@@ -24,6 +25,9 @@ use vulkano::{
 };
 use vulkano_util::renderer::{DeviceImageView, SwapchainImageView};
 use crate::app::SlimeApp;
+use crate::gui::Gui;
+use crate::profiler::GpuTimer;
+use crate::FRAMES_IN_FLIGHT;
 
 
 
@@ -67,6 +71,7 @@ pub struct RenderPassOverFrame{
     render_pass: Arc<RenderPass>,
     pixels_draw_pipeline: PixelDrawPipeline,
     command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    timer: Option<GpuTimer>,
 }
 
 impl RenderPassOverFrame {
@@ -75,7 +80,8 @@ impl RenderPassOverFrame {
         queue: Arc<Queue>,
         output_format: Format,
     ) -> RenderPassOverFrame {
-        let render_pass = vulkano::single_pass_renderpass!(
+        // the simulation is drawn in the first subpass and the gui over it in the second
+        let render_pass = vulkano::ordered_passes_renderpass!(
             queue.device().clone(),
             attachments: {
                 color: {
@@ -85,30 +91,58 @@ impl RenderPassOverFrame {
                     samples: 1,
                 },
             },
-            pass: {
-                color: [color],
-                depth_stencil: {},
-            },
+            passes: [
+                {
+                    color: [color],
+                    depth_stencil: {},
+                    input: [],
+                },
+                {
+                    color: [color],
+                    depth_stencil: {},
+                    input: [],
+                },
+            ],
         )
         .unwrap();
+        debug!("render pass output format {output_format:?}");
         let subpass = Subpass::from(render_pass.clone(), 0).unwrap();
         let pixels_draw_pipeline = PixelDrawPipeline::new(app, queue.clone(), subpass);
+        let timer = if app.profile {
+            GpuTimer::new(&queue, &["render"], FRAMES_IN_FLIGHT)
+        } else {
+            None
+        };
 
         RenderPassOverFrame {
             queue,
             render_pass,
             pixels_draw_pipeline,
             command_buffer_allocator: app.command_buffer_allocator.clone(),
+            timer,
         }
     }
 
+    /// The subpass the gui draws in, over the simulation.
+    pub fn gui_subpass(&self) -> Subpass {
+        Subpass::from(self.render_pass.clone(), 1).unwrap()
+    }
+
+    /// Rolling average GPU time of the render pass in milliseconds, empty unless profiling.
+    pub fn pass_timings(&self) -> Vec<(&'static str, f32)> {
+        self.timer.as_ref().map(GpuTimer::averages).unwrap_or_default()
+    }
+
     /// Places the view exactly over the target swapchain image. The texture draw pipeline uses a
-    /// quad onto which it places the view.
+    /// quad onto which it places the view, then draws `gui` over it if there is one. `frame` is the
+    /// frame in flight slot being recorded.
     pub fn render<F>(
-        &self,
+        &mut self,
         before_future: F,
         view: DeviceImageView,
         target: SwapchainImageView,
+        frame: usize,
+        mut gui: Option<&mut Gui>,
     ) -> Box<dyn GpuFuture>
     where
         F: GpuFuture + 'static,
@@ -134,6 +168,15 @@ impl RenderPassOverFrame {
         )
         .unwrap();
 
+        if let Some(timer) = &mut self.timer {
+            timer.reset(&mut command_buffer_builder, frame);
+            timer.begin(&mut command_buffer_builder, 0);
+        }
+
+        if let Some(gui) = &mut gui {
+            gui.upload(&mut command_buffer_builder);
+        }
+
         // Begin the render pass.
         command_buffer_builder
             .begin_render_pass(
@@ -153,9 +196,21 @@ impl RenderPassOverFrame {
         // Execute above commands (subpass).
         command_buffer_builder.execute_commands(cb).unwrap();
 
+        command_buffer_builder
+            .next_subpass(SubpassContents::SecondaryCommandBuffers)
+            .unwrap();
+        if let Some(gui) = gui {
+            let gui_cb = gui.draw(img_dims.width_height());
+            command_buffer_builder.execute_commands(gui_cb).unwrap();
+        }
+
         // End the render pass.
         command_buffer_builder.end_render_pass().unwrap();
 
+        if let Some(timer) = &self.timer {
+            timer.end(&mut command_buffer_builder, 0);
+        }
+
         // Build the command buffer.
         let command_buffer = command_buffer_builder.build().unwrap();
 
@@ -177,6 +232,10 @@ struct PixelDrawPipeline {
     descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
     vertices: Subbuffer<[TexturedVertex]>,
     indices: Subbuffer<[u32]>,
+    sampler: Arc<Sampler>,
+    /// Descriptor sets for the most recent images drawn, one per frame in flight since the
+    /// compute pipeline alternates between that many display images.
+    image_sets: Vec<(Arc<dyn ImageViewAbstract>, Arc<PersistentDescriptorSet>)>,
 }
 
 impl PixelDrawPipeline {
@@ -224,6 +283,18 @@ impl PixelDrawPipeline {
                 .unwrap()
         };
 
+        let sampler = Sampler::new(
+            queue.device().clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Nearest,
+                min_filter: Filter::Nearest,
+                address_mode: [SamplerAddressMode::Repeat; 3],
+                mipmap_mode: SamplerMipmapMode::Nearest,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
         PixelDrawPipeline {
             queue,
             subpass,
@@ -232,41 +303,41 @@ impl PixelDrawPipeline {
             descriptor_set_allocator: app.descriptor_set_allocator.clone(),
             vertices: vertex_buffer,
             indices: index_buffer,
+            sampler,
+            image_sets: Vec::new(),
         }
     }
 
     fn create_image_sampler_nearest(
-        &self,
+        &mut self,
         image: Arc<dyn ImageViewAbstract>,
     ) -> Arc<PersistentDescriptorSet> {
-        let layout = self.pipeline.layout().set_layouts().get(0).unwrap();
-        let sampler = Sampler::new(
-            self.queue.device().clone(),
-            SamplerCreateInfo {
-                mag_filter: Filter::Nearest,
-                min_filter: Filter::Nearest,
-                address_mode: [SamplerAddressMode::Repeat; 3],
-                mipmap_mode: SamplerMipmapMode::Nearest,
-                ..Default::default()
-            },
-        )
-        .unwrap();
+        if let Some((_, set)) = self.image_sets.iter().find(|(cached, _)| Arc::ptr_eq(cached, &image)) {
+            return set.clone();
+        }
 
-        PersistentDescriptorSet::new(
+        let layout = self.pipeline.layout().set_layouts().get(0).unwrap();
+        let set = PersistentDescriptorSet::new(
             &self.descriptor_set_allocator,
             layout.clone(),
             [WriteDescriptorSet::image_view_sampler(
                 0,
                 image.clone(),
-                sampler,
+                self.sampler.clone(),
             )],
         )
-        .unwrap()
+        .unwrap();
+
+        if self.image_sets.len() == FRAMES_IN_FLIGHT {
+            self.image_sets.remove(0);
+        }
+        self.image_sets.push((image, set.clone()));
+        set
     }
 
     /// Draws input `image` over a quad of size -1.0 to 1.0.
     pub fn draw(
-        &self,
+        &mut self,
         viewport_dimensions: [u32; 2],
         image: Arc<dyn ImageViewAbstract>,
     ) -> SecondaryAutoCommandBuffer {
@@ -337,7 +408,8 @@ mod fs {
             layout(set = 0, binding = 0) uniform sampler2D tex;
 
             void main() {
-                f_color = texture(tex, v_tex_coords);
+                // alpha holds the trail strength rather than coverage
+                f_color = vec4(texture(tex, v_tex_coords).rgb, 1.0);
             }
         ",
     }
@@ -0,0 +1,252 @@
+use std::error::Error;
+use std::f32::consts::PI;
+use std::fmt;
+use std::path::Path;
+use crate::animation::{Interpolation, Keyframe, Param, Track};
+
+
+/// Adds agents continuously over a disc.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Emitter {
+    /// Centre in simulation pixels.
+    pub pos: [f32; 2],
+    /// Agents emitted per second.
+    pub rate: f32,
+    pub radius: f32,
+    /// Radians the agents head off in on average.
+    pub direction: f32,
+    /// Radians either side of `direction` the agents can head off in, PI for every direction.
+    pub spread: f32,
+    /// Tag stored on every agent the emitter spawns, which picks the colour of their trail.
+    pub species: u32,
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Emitter {
+            pos: [0.0, 0.0],
+            rate: 100.0,
+            radius: 0.0,
+            direction: 0.0,
+            spread: PI,
+            species: 0,
+        }
+    }
+}
+
+
+/// Removes every agent that walks into a disc.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sink {
+    /// Centre in simulation pixels.
+    pub pos: [f32; 2],
+    pub radius: f32,
+}
+
+
+/// Lays trail down over a disc every step, which draws the agents in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Food {
+    /// Centre in simulation pixels.
+    pub pos: [f32; 2],
+    pub radius: f32,
+    /// Trail added to each pixel per second, the trail is capped at 1.
+    pub strength: f32,
+}
+
+impl Default for Food {
+    fn default() -> Self {
+        Food {
+            pos: [0.0, 0.0],
+            radius: 8.0,
+            strength: 5.0,
+        }
+    }
+}
+
+
+/// Emitters, sinks and food that act on the simulation for the whole run, and keyframed
+/// parameters.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scene {
+    pub emitters: Vec<Emitter>,
+    pub sinks: Vec<Sink>,
+    pub foods: Vec<Food>,
+    /// At most one per parameter.
+    pub tracks: Vec<Track>,
+}
+
+impl Scene {
+    /// Reads a scene file, one emitter, sink, food or keyframe per line with `key=value` fields:
+    ///
+    /// ```text
+    /// # a river from the left edge into a drain on the right
+    /// emitter pos=100,540 rate=2000 radius=20 direction=0 spread=15 species=0
+    /// sink pos=1800,540 radius=40
+    /// food pos=960,200 radius=8 strength=5
+    /// # widen the sensors over the first ten seconds at 60fps
+    /// keyframe param=sensor_angle frame=0 value=30 interpolation=smoothstep
+    /// keyframe param=sensor_angle frame=600 value=75
+    /// ```
+    ///
+    /// Angles are in degrees. Keyframes take a [`SimParams`](crate::params::SimParams) field name,
+    /// a frame counted in steps since the start, and `linear` (the default), `smoothstep` or
+    /// `cubic` interpolation towards the next keyframe.
+    ///
+    /// Emitter and food fields left out take the [`Default`] values, blank lines and lines starting
+    /// with `#` are skipped.
+    pub fn load(path: impl AsRef<Path>) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| SceneError {
+            line: 0,
+            message: format!("can't read {}: {e}", path.display()),
+        })?;
+        text.parse()
+    }
+}
+
+impl std::str::FromStr for Scene {
+    type Err = SceneError;
+
+    fn from_str(text: &str) -> Result<Scene, SceneError> {
+        let mut scene = Scene::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| SceneError { line: index + 1, message };
+
+            let mut words = line.split_whitespace();
+            let kind = words.next().unwrap();
+            let fields = words.map(|word| {
+                word.split_once('=')
+                    .ok_or_else(|| error(format!("expected key=value, got '{word}'")))
+            });
+            match kind {
+                "emitter" => {
+                    let mut emitter = Emitter::default();
+                    for field in fields {
+                        let (key, value) = field?;
+                        match key {
+                            "pos" => emitter.pos = parse_pair(value).map_err(error)?,
+                            "rate" => emitter.rate = parse_number(value).map_err(error)?,
+                            "radius" => emitter.radius = parse_number(value).map_err(error)?,
+                            "direction" => emitter.direction = parse_number(value).map_err(error)?.to_radians(),
+                            "spread" => emitter.spread = parse_number(value).map_err(error)?.to_radians(),
+                            "species" => {
+                                emitter.species = value
+                                    .parse()
+                                    .map_err(|_| error(format!("species expects an index, got '{value}'")))?
+                            }
+                            _ => return Err(error(format!("unknown emitter field '{key}'"))),
+                        }
+                    }
+                    scene.emitters.push(emitter);
+                }
+                "sink" => {
+                    let mut sink = Sink { pos: [0.0, 0.0], radius: 0.0 };
+                    for field in fields {
+                        let (key, value) = field?;
+                        match key {
+                            "pos" => sink.pos = parse_pair(value).map_err(error)?,
+                            "radius" => sink.radius = parse_number(value).map_err(error)?,
+                            _ => return Err(error(format!("unknown sink field '{key}'"))),
+                        }
+                    }
+                    scene.sinks.push(sink);
+                }
+                "food" => {
+                    let mut food = Food::default();
+                    for field in fields {
+                        let (key, value) = field?;
+                        match key {
+                            "pos" => food.pos = parse_pair(value).map_err(error)?,
+                            "radius" => food.radius = parse_number(value).map_err(error)?,
+                            "strength" => food.strength = parse_number(value).map_err(error)?,
+                            _ => return Err(error(format!("unknown food field '{key}'"))),
+                        }
+                    }
+                    scene.foods.push(food);
+                }
+                "keyframe" => {
+                    let mut param = None;
+                    let mut frame = None;
+                    let mut value = None;
+                    let mut interpolation = Interpolation::Linear;
+                    for field in fields {
+                        let (key, text) = field?;
+                        match key {
+                            "param" => {
+                                param = Some(Param::from_name(text).ok_or_else(|| error(format!("unknown parameter '{text}'")))?)
+                            }
+                            "frame" => {
+                                frame = Some(text.parse().map_err(|_| error(format!("frame expects a frame number, got '{text}'")))?)
+                            }
+                            "value" => value = Some(parse_number(text).map_err(error)?),
+                            "interpolation" => {
+                                interpolation = match text {
+                                    "linear" => Interpolation::Linear,
+                                    "smoothstep" => Interpolation::Smoothstep,
+                                    "cubic" => Interpolation::Cubic,
+                                    _ => return Err(error(format!("unknown interpolation '{text}'"))),
+                                }
+                            }
+                            _ => return Err(error(format!("unknown keyframe field '{key}'"))),
+                        }
+                    }
+                    let (Some(param), Some(frame), Some(mut value)) = (param, frame, value) else {
+                        return Err(error("keyframe needs a param, frame and value".to_string()));
+                    };
+                    if param == Param::SensorAngle {
+                        value = value.to_radians();
+                    }
+
+                    let key = Keyframe { frame, value, interpolation };
+                    match scene.tracks.iter_mut().find(|track| track.param == param) {
+                        Some(track) => track.insert(key),
+                        None => {
+                            let mut track = Track::new(param);
+                            track.insert(key);
+                            scene.tracks.push(track);
+                        }
+                    }
+                }
+                _ => return Err(error(format!("unknown entry '{kind}', expected emitter, sink, food or keyframe"))),
+            }
+        }
+        Ok(scene)
+    }
+}
+
+
+fn parse_number(value: &str) -> Result<f32, String> {
+    value.parse().map_err(|_| format!("expected a number, got '{value}'"))
+}
+
+fn parse_pair(value: &str) -> Result<[f32; 2], String> {
+    let (x, y) = value
+        .split_once(',')
+        .ok_or_else(|| format!("expected x,y, got '{value}'"))?;
+    Ok([parse_number(x)?, parse_number(y)?])
+}
+
+
+#[derive(Debug)]
+pub struct SceneError {
+    /// 1 based, 0 if the file couldn't be read at all.
+    line: usize,
+    message: String,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "scene line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl Error for SceneError {}
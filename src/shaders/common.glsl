@@ -0,0 +1,238 @@
+#define M_PI 3.1415926535897932384626433832795
+
+struct SlimeAgent {
+    vec2 pos;
+    float angle;
+    // generator state, seeded on the host and advanced every step
+    uint rng;
+    // seconds since the agent was spawned
+    float age;
+    // gained from the trail under the agent, spent over time, the agent starves at zero
+    float energy;
+    // set by the emitter that spawned the agent
+    uint species;
+    // keeps the size a multiple of the struct's 8 byte alignment so arrays of it aren't padded
+    uint _pad;
+};
+
+struct Emitter {
+    vec2 pos;
+    float radius;
+    float direction;
+    // radians either side of direction
+    float spread;
+    uint species;
+    // this step's agents from the emitter are emitted[first, first + count)
+    uint first;
+    uint count;
+};
+
+// circular regions that remove the agents that walk into them
+struct Sink {
+    vec2 pos;
+    float radius;
+    uint _pad;
+};
+
+// circular regions that lay trail down every step, attracting the agents
+struct Food {
+    vec2 pos;
+    float radius;
+    // trail added per second
+    float strength;
+};
+
+
+// half floats, rgb is what gets drawn, alpha is the trail strength the agents sense so it doesn't
+// depend on the colour
+layout(set = 0, binding = 0, rgba16f) uniform image2D img;
+
+// a faint enough half float times the decay factor rounds back to itself, so the decay never takes
+// it to zero on its own and anything weaker than this is cleared instead
+const float TRAIL_CUTOFF = 1.0 / 1024.0;
+
+
+layout(set = 0, binding = 1) buffer Agents {
+    SlimeAgent[] agents;
+};
+
+// the survivors and offspring of this step when agents can die or split, the host swaps it with
+// agents for the next step
+layout(set = 0, binding = 5) buffer AgentsOut {
+    SlimeAgent[] agents_out;
+};
+
+layout(set = 0, binding = 6) buffer AgentCounts {
+    // live agents at the start of agents
+    uint alive;
+    // agents appended to agents_out so far this step, can run past the capacity
+    uint next_alive;
+};
+
+// workgroups for the agent update, filled in by the prepare pass
+layout(set = 0, binding = 7) buffer DispatchArgs {
+    uint groups_x;
+    uint groups_y;
+    uint groups_z;
+};
+
+// agents added by the host, appended to agents by the spawn pass
+layout(set = 0, binding = 8) buffer Spawned {
+    SlimeAgent[] spawned;
+};
+
+layout(set = 0, binding = 9) buffer Emitters {
+    Emitter emitters[];
+};
+
+layout(set = 0, binding = 10) buffer Sinks {
+    Sink sinks[];
+};
+
+layout(set = 0, binding = 13) buffer Foods {
+    Food foods[];
+};
+
+// have to match HISTOGRAM_BINS and HEADING_BINS in stats.rs
+#define HISTOGRAM_BINS 32
+#define HEADING_BINS 32
+
+// totted up by the stats passes, zeroed by the host before each step they run in
+layout(set = 0, binding = 11) buffer StatsCounts {
+    // bits of the strongest trail pixel, strengths are never negative so these order like the floats
+    uint max_strength;
+    // pixels at or above the coverage threshold
+    uint covered;
+    // pixels by strength, in equal bins from 0 to 1
+    uint histogram[HISTOGRAM_BINS];
+    // live agents by heading, in equal bins from 0 to 2 pi
+    uint headings[HEADING_BINS];
+};
+
+// the trail strength summed over each of the trail stats pass's workgroups, added up on the host
+layout(set = 0, binding = 12) buffer PartialSums {
+    float partial_sums[];
+};
+
+// holds the horizontally blurred trail between the two diffuse passes
+layout(set = 0, binding = 2, rgba16f) uniform image2D blur_img;
+
+// has to match MAX_SPECIES on the host
+#define MAX_SPECIES 4
+
+// trail laid down by the agents this step, added onto img by the deposit pass. each species has a
+// layer of one entry per pixel, so its trail can be drawn in its own colour
+#ifdef FLOAT_ATOMICS
+layout(set = 0, binding = 3) buffer FloatDeposits {
+    float deposits[];
+};
+#else
+// fixed point with 16 fractional bits for devices without float atomics
+const float DEPOSIT_SCALE = 65536.0;
+
+layout(set = 0, binding = 3) buffer FixedDeposits {
+    uint deposits[];
+};
+#endif
+
+// simulation settings, only rewritten by the host when they change
+layout(set = 0, binding = 4) uniform SimSettings {
+    // colour each species lays down with its trail, alpha unused
+    vec4 species_colours[MAX_SPECIES];
+
+    int width;
+    int height;
+
+    // every rate below is per second
+    float turn_speed;
+    float move_speed;
+    float sense_distance;
+    float sensor_angle;
+
+    float decay_rate;
+    float diffuse_rate;
+    float deposit_amount;
+
+    // energy every agent spawns with
+    float initial_energy;
+    // lifecycle, zero turns each rule off
+    float max_age;
+    float energy_gain;
+    float energy_cost;
+    float split_energy;
+
+    uint num_sinks;
+    uint num_foods;
+    // layers of deposits, agents of any higher species share the last
+    uint num_species;
+    // nonzero when agents can die or split, so the update compacts them into agents_out. Otherwise
+    // they are updated in place and keep their order
+    uint compact_agents;
+} params;
+
+// values that change every step
+layout(push_constant) uniform PushConstants {
+    // seconds since the last step
+    float delta_time;
+    // steps since init
+    uint frame;
+    // agents waiting in spawned
+    uint num_spawned;
+    // size of agents and agents_out
+    uint capacity;
+    // the population is cut down to this many before the step
+    uint max_alive;
+    // agents added by the emitters this step, and how many emitters there are
+    uint num_emitted;
+    uint num_emitters;
+    // drawn from the host's seeded generator every step the emitters add agents
+    uint emit_seed;
+} push_constants;
+
+
+// Hash function www.cs.ubc.ca/~rbridson/docs/schechter-sca08-turbulence.pdf
+uint hash(uint state)
+{
+    state ^= 2747636419u;
+    state *= 2654435769u;
+    state ^= state >> 16;
+    state *= 2654435769u;
+    state ^= state >> 16;
+    state *= 2654435769u;
+    return state;
+}
+
+float scaleToRange01(uint state)
+{
+    return state / 4294967295.0;
+}
+
+// Unnormalised gaussian weight for a blur of the given radius, sigma grows with the radius so a
+// radius of 1 is close to the old 3x3 box blur.
+float gaussian_weight(int offset, int radius)
+{
+    float sigma = (radius + 1) * 0.5;
+    return exp(-float(offset * offset) / (2.0 * sigma * sigma));
+}
+
+void add_deposit(uint index, float amount)
+{
+#ifdef FLOAT_ATOMICS
+    atomicAdd(deposits[index], amount);
+#else
+    atomicAdd(deposits[index], uint(amount * DEPOSIT_SCALE));
+#endif
+}
+
+// reads the deposit for a pixel and clears it for the next step
+float take_deposit(uint index)
+{
+#ifdef FLOAT_ATOMICS
+    float amount = deposits[index];
+    deposits[index] = 0.0;
+#else
+    float amount = deposits[index] / DEPOSIT_SCALE;
+    deposits[index] = 0u;
+#endif
+    return amount;
+}
@@ -0,0 +1,37 @@
+// included by deposit_fixed.glsl and deposit_float.glsl
+#include "common.glsl"
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+
+// adds this step's deposits from every species, and the food's, onto the trail map
+void main() {
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(img);
+
+    if (any(greaterThanEqual(pos, size))) {
+        return;
+    }
+
+    uint pixel = pos.y * size.x + pos.x;
+    uint layer_size = size.x * size.y;
+    vec3 colour = vec3(0.0);
+    float amount = 0.0;
+    for (uint species = 0; species < params.num_species; species++) {
+        float deposit = take_deposit(species * layer_size + pixel);
+        colour += params.species_colours[species].rgb * deposit;
+        amount += deposit;
+    }
+    // food lays trail in the first species' colour
+    for (uint i = 0; i < params.num_foods; i++) {
+        if (distance(vec2(pos), foods[i].pos) < foods[i].radius) {
+            float deposit = foods[i].strength * push_constants.delta_time;
+            colour += params.species_colours[0].rgb * deposit;
+            amount += deposit;
+        }
+    }
+    if (amount > 0.0) {
+        vec4 trail = imageLoad(img, pos) + vec4(colour, amount);
+        imageStore(img, pos, min(trail, vec4(1.0)));
+    }
+}
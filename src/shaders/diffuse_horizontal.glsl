@@ -0,0 +1,40 @@
+#version 460
+#include "common.glsl"
+
+#define TILE_SIZE 16
+
+layout(local_size_x = TILE_SIZE, local_size_y = TILE_SIZE, local_size_z = 1) in;
+
+layout(constant_id = 0) const int blur_radius = 1;
+
+// each row of the tile carries blur_radius extra pixels on both sides
+shared vec4 tile[TILE_SIZE][TILE_SIZE + 2 * blur_radius];
+
+
+// First half of the separable gaussian, blurs img along x into blur_img.
+void main() {
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 local = ivec2(gl_LocalInvocationID.xy);
+    int tile_start = int(gl_WorkGroupID.x) * TILE_SIZE - blur_radius;
+    int row = clamp(pos.y, 0, params.height - 1);
+
+    for (int x = local.x; x < TILE_SIZE + 2 * blur_radius; x += TILE_SIZE) {
+        int sample_x = clamp(tile_start + x, 0, params.width - 1);
+        tile[local.y][x] = imageLoad(img, ivec2(sample_x, row));
+    }
+    barrier();
+
+    if (pos.x >= params.width || pos.y >= params.height) {
+        return;
+    }
+
+    vec4 sum = vec4(0.0);
+    float total_weight = 0.0;
+    for (int offset = -blur_radius; offset <= blur_radius; offset ++) {
+        float weight = gaussian_weight(offset, blur_radius);
+        sum += tile[local.y][local.x + blur_radius + offset] * weight;
+        total_weight += weight;
+    }
+
+    imageStore(blur_img, pos, sum / total_weight);
+}
@@ -0,0 +1,51 @@
+#version 460
+#include "common.glsl"
+
+#define TILE_SIZE 16
+
+layout(local_size_x = TILE_SIZE, local_size_y = TILE_SIZE, local_size_z = 1) in;
+
+layout(constant_id = 0) const int blur_radius = 1;
+
+// each column of the tile carries blur_radius extra pixels above and below
+shared vec4 tile[TILE_SIZE + 2 * blur_radius][TILE_SIZE];
+
+
+// Second half of the separable gaussian, blurs blur_img along y then mixes the result into img
+// by the diffuse rate and applies decay.
+void main() {
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 local = ivec2(gl_LocalInvocationID.xy);
+    int tile_start = int(gl_WorkGroupID.y) * TILE_SIZE - blur_radius;
+    int column = clamp(pos.x, 0, params.width - 1);
+
+    for (int y = local.y; y < TILE_SIZE + 2 * blur_radius; y += TILE_SIZE) {
+        int sample_y = clamp(tile_start + y, 0, params.height - 1);
+        tile[y][local.x] = imageLoad(blur_img, ivec2(column, sample_y));
+    }
+    barrier();
+
+    if (pos.x >= params.width || pos.y >= params.height) {
+        return;
+    }
+
+    vec4 sum = vec4(0.0);
+    float total_weight = 0.0;
+    for (int offset = -blur_radius; offset <= blur_radius; offset ++) {
+        float weight = gaussian_weight(offset, blur_radius);
+        sum += tile[local.y + blur_radius + offset][local.x] * weight;
+        total_weight += weight;
+    }
+
+	vec4 blurredCol = sum / total_weight;
+	vec4 originalCol = imageLoad(img, pos);
+	// exponential in time so the result doesn't depend on how often steps run
+	float diffuseWeight = 1.0 - exp(-params.diffuse_rate * push_constants.delta_time);
+	blurredCol = originalCol * (1 - diffuseWeight) + blurredCol * (diffuseWeight);
+
+    vec4 new_col = blurredCol * exp(-params.decay_rate * push_constants.delta_time);
+    if (params.decay_rate > 0.0 && new_col.a < TRAIL_CUTOFF) {
+        new_col = vec4(0.0);
+    }
+    imageStore(img, pos, new_col);
+}
@@ -0,0 +1,45 @@
+#version 460
+#include "common.glsl"
+
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+
+// spawns this step's agents from the emitters after the live and spawned ones, dropping any that
+// don't fit. the prepare pass adds them to the live count
+void main() {
+    uint id = gl_GlobalInvocationID.y * gl_NumWorkGroups.x * gl_WorkGroupSize.x + gl_GlobalInvocationID.x;
+
+    if (id >= push_constants.num_emitted) {
+        return;
+    }
+
+    // there are only ever a handful of emitters, so a linear search beats anything cleverer
+    Emitter emitter = emitters[0];
+    for (uint i = 0; i < push_constants.num_emitters; i++) {
+        if (id < emitters[i].first + emitters[i].count) {
+            emitter = emitters[i];
+            break;
+        }
+    }
+
+    uint state = hash(push_constants.emit_seed ^ hash(id));
+    float r = sqrt(scaleToRange01(state)) * emitter.radius;
+    state = hash(state);
+    float theta = scaleToRange01(state) * 2 * M_PI;
+    state = hash(state);
+
+    SlimeAgent agent;
+    agent.pos = emitter.pos + r * vec2(cos(theta), sin(theta));
+    agent.angle = emitter.direction + (scaleToRange01(state) * 2.0 - 1.0) * emitter.spread;
+    // never zero so xorshift can't get stuck
+    agent.rng = max(hash(state), 1u);
+    agent.age = 0.0;
+    agent.energy = params.initial_energy;
+    agent.species = emitter.species;
+    agent._pad = 0;
+
+    uint index = alive + push_constants.num_spawned + id;
+    if (index < push_constants.capacity) {
+        agents[index] = agent;
+    }
+}
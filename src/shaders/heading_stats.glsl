@@ -0,0 +1,29 @@
+#version 460
+#include "common.glsl"
+
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+shared uint group_headings[HEADING_BINS];
+
+
+// Counts the live agents going into the step by the way they face, dispatched with the agent
+// update's indirect arguments.
+void main() {
+    uint local = gl_LocalInvocationIndex;
+    if (local < HEADING_BINS) {
+        group_headings[local] = 0;
+    }
+    barrier();
+
+    uint id = gl_GlobalInvocationID.y * gl_NumWorkGroups.x * gl_WorkGroupSize.x + gl_GlobalInvocationID.x;
+    if (id < alive) {
+        float turns = mod(agents[id].angle, 2.0 * M_PI) / (2.0 * M_PI);
+        uint bin = min(uint(turns * HEADING_BINS), HEADING_BINS - 1);
+        atomicAdd(group_headings[bin], 1);
+    }
+    barrier();
+
+    if (local < HEADING_BINS) {
+        atomicAdd(headings[local], group_headings[local]);
+    }
+}
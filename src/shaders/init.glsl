@@ -0,0 +1,16 @@
+#version 460
+#include "common.glsl"
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+
+// fills image with black and no trail
+void main() {
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+
+    if (any(greaterThanEqual(pos, imageSize(img)))) {
+        return;
+    }
+
+    imageStore(img, pos, vec4(0.0));
+}
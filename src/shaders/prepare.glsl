@@ -0,0 +1,26 @@
+#version 460
+#include "common.glsl"
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+// has to match the agent update's local_size_x
+#define AGENT_GROUP_SIZE 64
+
+// the device's maxComputeWorkGroupCount[0]
+layout(constant_id = 0) const uint max_groups_x = 65535;
+
+
+// Counts in the agents spawned and emitted this step, clamps the population and sizes the agent
+// update's indirect dispatch to it, in rows of at most max_groups_x groups like the host does for
+// direct dispatches.
+void main() {
+    uint added = alive + push_constants.num_spawned + push_constants.num_emitted;
+    alive = min(added, min(push_constants.capacity, push_constants.max_alive));
+    next_alive = 0;
+
+    uint groups = (alive + AGENT_GROUP_SIZE - 1) / AGENT_GROUP_SIZE;
+    uint row = clamp(groups, 1, max_groups_x);
+    groups_x = row;
+    groups_y = (groups + row - 1) / row;
+    groups_z = 1;
+}
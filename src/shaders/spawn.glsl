@@ -0,0 +1,21 @@
+#version 460
+#include "common.glsl"
+
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+
+// appends the agents the host added this step after the live ones, dropping any that don't fit.
+// each goes in its own slot rather than one from an atomic counter so they keep the host's order,
+// the prepare pass adds them to the live count
+void main() {
+    uint id = gl_GlobalInvocationID.y * gl_NumWorkGroups.x * gl_WorkGroupSize.x + gl_GlobalInvocationID.x;
+
+    if (id >= push_constants.num_spawned) {
+        return;
+    }
+
+    uint index = alive + id;
+    if (index < push_constants.capacity) {
+        agents[index] = spawned[id];
+    }
+}
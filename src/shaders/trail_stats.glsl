@@ -0,0 +1,58 @@
+#version 460
+#include "common.glsl"
+
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+
+// pixels at least this strong count towards the coverage
+layout(constant_id = 0) const float coverage_threshold = 0.1;
+
+shared float group_sums[256];
+shared float group_maxes[256];
+shared uint group_covered;
+shared uint group_histogram[HISTOGRAM_BINS];
+
+
+// Reduces each workgroup's tile of the trail in shared memory, then adds it into the stats with one
+// atomic per value. The sums are left per workgroup as adding floats atomically isn't universal.
+void main() {
+    uint local = gl_LocalInvocationIndex;
+    if (local == 0) {
+        group_covered = 0;
+    }
+    if (local < HISTOGRAM_BINS) {
+        group_histogram[local] = 0;
+    }
+    barrier();
+
+    ivec2 pos = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(img);
+    float strength = 0.0;
+    if (all(lessThan(pos, size))) {
+        strength = max(imageLoad(img, pos).a, 0.0);
+        uint bin = min(uint(strength * HISTOGRAM_BINS), HISTOGRAM_BINS - 1);
+        atomicAdd(group_histogram[bin], 1);
+        if (strength >= coverage_threshold) {
+            atomicAdd(group_covered, 1);
+        }
+    }
+    group_sums[local] = strength;
+    group_maxes[local] = strength;
+    barrier();
+
+    for (uint stride = 128; stride > 0; stride >>= 1) {
+        if (local < stride) {
+            group_sums[local] += group_sums[local + stride];
+            group_maxes[local] = max(group_maxes[local], group_maxes[local + stride]);
+        }
+        barrier();
+    }
+
+    if (local == 0) {
+        partial_sums[gl_WorkGroupID.y * gl_NumWorkGroups.x + gl_WorkGroupID.x] = group_sums[0];
+        atomicMax(max_strength, floatBitsToUint(group_maxes[0]));
+        atomicAdd(covered, group_covered);
+    }
+    if (local < HISTOGRAM_BINS) {
+        atomicAdd(histogram[local], group_histogram[local]);
+    }
+}
@@ -0,0 +1,235 @@
+// included by update_fixed.glsl and update_float.glsl
+#include "common.glsl"
+
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+// specialised so the sensing loops have a fixed trip count and can be unrolled
+layout(constant_id = 0) const int sensor_size = 1;
+// 0 = square, 1 = circle, 2 = gaussian
+layout(constant_id = 1) const int sensor_kernel = 0;
+// sample the trail and deposit between pixels instead of truncating positions
+layout(constant_id = 2) const bool sub_pixel = false;
+
+// 0 = position hash, 1 = pcg, 2 = xorshift
+layout(constant_id = 3) const int rng_algorithm = 1;
+
+#define KERNEL_SQUARE 0
+#define KERNEL_CIRCLE 1
+#define KERNEL_GAUSSIAN 2
+
+#define RNG_HASH 0
+#define RNG_PCG 1
+#define RNG_XORSHIFT 2
+
+
+// Advances the agent's generator and returns the next value. RNG_HASH ignores the state and
+// hashes hash_seed instead, which is how agents were steered before they had their own state.
+uint next_random(inout uint state, uint hash_seed) {
+    if (rng_algorithm == RNG_PCG) {
+        // pcg-rxs-m-xs 32
+        state = state * 747796405u + 2891336453u;
+        uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+        return (word >> 22u) ^ word;
+    } else if (rng_algorithm == RNG_XORSHIFT) {
+        // xorshift32, the state is never zero as the host seeds it non zero
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        return state;
+    }
+    return hash(hash_seed);
+}
+
+
+float trail_at(ivec2 pixel) {
+    pixel = clamp(pixel, ivec2(0), ivec2(params.width - 1, params.height - 1));
+    return imageLoad(img, pixel).a;
+}
+
+// pixel values sit at pixel centres, so a position is interpolated between the four nearest
+float trail_bilinear(vec2 pos) {
+    vec2 corner = pos - 0.5;
+    ivec2 base = ivec2(floor(corner));
+    vec2 t = corner - vec2(base);
+
+    float top = mix(trail_at(base), trail_at(base + ivec2(1, 0)), t.x);
+    float bottom = mix(trail_at(base + ivec2(0, 1)), trail_at(base + ivec2(1, 1)), t.x);
+    return mix(top, bottom, t.y);
+}
+
+float kernel_weight(int offset_x, int offset_y) {
+    float dist_sq = float(offset_x * offset_x + offset_y * offset_y);
+    if (sensor_kernel == KERNEL_CIRCLE) {
+        float radius = sensor_size + 0.5;
+        return dist_sq <= radius * radius ? 1.0 : 0.0;
+    } else if (sensor_kernel == KERNEL_GAUSSIAN) {
+        float sigma = max(sensor_size * 0.5, 0.5);
+        return exp(-dist_sq / (2.0 * sigma * sigma));
+    }
+    return 1.0;
+}
+
+float sense(SlimeAgent agent, float sensor_angle_offset) {
+    float sensor_angle = agent.angle + sensor_angle_offset;
+    vec2 sensor_dir = vec2(cos(sensor_angle), sin(sensor_angle));
+
+    vec2 sensor_centre = agent.pos + sensor_dir * params.sense_distance;
+    int centre_x = clamp(int(sensor_centre.x), 0, params.width - 1);
+    int centre_y = clamp(int(sensor_centre.y), 0, params.height - 1);
+
+    float sum = 0;
+
+	for (int offset_x = -sensor_size; offset_x <= sensor_size; offset_x ++) {
+		for (int offset_y = -sensor_size; offset_y <= sensor_size; offset_y ++) {
+			float weight = kernel_weight(offset_x, offset_y);
+			if (weight == 0.0) {
+				continue;
+			}
+
+			if (sub_pixel) {
+				sum += weight * trail_bilinear(sensor_centre + vec2(offset_x, offset_y));
+			} else {
+				sum += weight * trail_at(ivec2(centre_x + offset_x, centre_y + offset_y));
+			}
+		}
+	}
+
+    return sum;
+}
+
+// layer is the offset of the species' deposits
+void deposit_at(uint layer, ivec2 pixel, float amount) {
+    ivec2 size = imageSize(img);
+    if (amount > 0.0 && all(greaterThanEqual(pixel, ivec2(0))) && all(lessThan(pixel, size))) {
+        add_deposit(layer + pixel.y * size.x + pixel.x, amount);
+    }
+}
+
+// spreads the deposit over the four pixels around pos by bilinear weights
+void splat(uint layer, vec2 pos, float amount) {
+    vec2 corner = pos - 0.5;
+    ivec2 base = ivec2(floor(corner));
+    vec2 t = corner - vec2(base);
+
+    deposit_at(layer, base, amount * (1.0 - t.x) * (1.0 - t.y));
+    deposit_at(layer, base + ivec2(1, 0), amount * t.x * (1.0 - t.y));
+    deposit_at(layer, base + ivec2(0, 1), amount * (1.0 - t.x) * t.y);
+    deposit_at(layer, base + ivec2(1, 1), amount * t.x * t.y);
+}
+
+
+// adds an agent to agents_out, dropped if the buffer is full
+void append(SlimeAgent agent) {
+    uint index = atomicAdd(next_alive, 1);
+    if (index < push_constants.capacity) {
+        agents_out[index] = agent;
+    }
+}
+
+
+void main() {
+    // dispatched in rows when there are more groups than one dimension allows
+    uint id = gl_GlobalInvocationID.y * gl_NumWorkGroups.x * gl_WorkGroupSize.x + gl_GlobalInvocationID.x;
+
+    if (id >= alive) {
+        return;
+    }
+
+    // process data
+    SlimeAgent agent = agents[id];
+    vec2 dir = vec2(cos(agent.angle), sin(agent.angle));
+    vec2 new_pos = agent.pos + dir * params.move_speed * push_constants.delta_time;
+
+    ivec2 pos = ivec2(agent.pos);
+    uint rng = agent.rng;
+    uint random = next_random(rng, pos.y * params.width + pos.x * hash(id));
+
+
+    // sensing
+
+    float random_steer = scaleToRange01(random);
+    float turn = params.turn_speed * push_constants.delta_time;
+    float angle = agent.angle;
+
+    float sensorAngleRad = params.sensor_angle;
+	float weightForward = sense(agent, 0);
+	float weightLeft = sense(agent, sensorAngleRad);
+	float weightRight = sense(agent, -sensorAngleRad);
+
+    // Continue in same direction
+	if (weightForward > weightLeft && weightForward > weightRight) {
+		angle += 0;
+	}
+	else if (weightForward < weightLeft && weightForward < weightRight) {
+		angle += (random_steer - 0.5) * 2 * turn;
+	}
+	// Turn right
+	else if (weightRight > weightLeft) {
+		angle -= random_steer * turn;
+	}
+	// Turn left
+	else if (weightLeft > weightRight) {
+		angle += random_steer * turn;
+	}
+
+
+    // bounce off image walls
+    if (new_pos.x < 0 || new_pos.x >= params.width || new_pos.y < 0 || new_pos.y >= params.height) {
+        new_pos.x = min(params.width - 1, max(0, new_pos.x));
+        new_pos.y = min(params.height - 1, max(0, new_pos.y));
+
+        angle = scaleToRange01(next_random(rng, random)) * 2 * M_PI;
+    }
+
+    agent.pos = new_pos;
+    agent.angle = angle;
+    agent.rng = rng;
+
+
+    // lifecycle, agents feed on the trail under them
+    agent.age += push_constants.delta_time;
+    agent.energy += (trail_at(ivec2(new_pos)) * params.energy_gain - params.energy_cost) * push_constants.delta_time;
+
+    bool starved = agent.energy <= 0.0;
+    bool too_old = params.max_age > 0.0 && agent.age >= params.max_age;
+    if (starved || too_old) {
+        return;
+    }
+
+    for (uint i = 0; i < params.num_sinks; i++) {
+        if (distance(new_pos, sinks[i].pos) < sinks[i].radius) {
+            return;
+        }
+    }
+
+
+    // lay down trail, atomically so agents sharing a pixel all count
+    float amount = params.deposit_amount * push_constants.delta_time;
+    ivec2 size = imageSize(img);
+    uint layer = min(agent.species, params.num_species - 1) * uint(size.x * size.y);
+    if (sub_pixel) {
+        splat(layer, new_pos, amount);
+    } else {
+        deposit_at(layer, ivec2(new_pos), amount);
+    }
+
+
+    // well fed agents split in two, the child heads off at up to 90 degrees from its parent
+    if (params.split_energy > 0.0 && agent.energy >= params.split_energy) {
+        agent.energy *= 0.5;
+
+        SlimeAgent child = agent;
+        child.age = 0.0;
+        child.angle += (scaleToRange01(next_random(agent.rng, random ^ id)) - 0.5) * M_PI;
+        // never zero so xorshift can't get stuck
+        child.rng = max(hash(agent.rng ^ id), 1u);
+        append(child);
+    }
+
+    if (params.compact_agents != 0u) {
+        append(agent);
+    } else {
+        // nothing can die or split, so the agent stays where it is
+        agents[id] = agent;
+    }
+}
@@ -0,0 +1,4 @@
+#version 460
+#extension GL_EXT_shader_atomic_float : require
+#define FLOAT_ATOMICS
+#include "update.glsl"
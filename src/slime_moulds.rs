@@ -1,37 +1,598 @@
-use std::sync::Arc;
+use std::{error::Error, fmt, sync::Arc};
+use log::{info, debug, trace, warn};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use half::f16;
 use vulkano::{
     device::Queue,
     pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
-    command_buffer::{allocator::StandardCommandBufferAllocator, AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer},
+    command_buffer::{allocator::StandardCommandBufferAllocator, AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, BufferCopy, CopyBufferInfo, CopyImageInfo, CopyImageToBufferInfo, DispatchIndirectCommand},
     descriptor_set::{allocator::StandardDescriptorSetAllocator, WriteDescriptorSet, PersistentDescriptorSet},
-    image::{ImageUsage, StorageImage},
+    image::{view::ImageView, ImageCreateFlags, ImageDimensions, ImageUsage, StorageImage},
     format::Format,
-    memory::allocator::{MemoryUsage, AllocationCreateInfo}, sync::GpuFuture, buffer::{Buffer, Subbuffer, BufferCreateInfo, BufferUsage}, padded::Padded, DeviceSize,
+    memory::allocator::{MemoryUsage, AllocationCreateInfo, StandardMemoryAllocator}, sync::{self, future::FenceSignalFuture, GpuFuture}, buffer::{Buffer, Subbuffer, BufferCreateInfo, BufferUsage}, DeviceSize,
+    shader::{EntryPoint, SpecializationConstants},
+    instance::{Instance, InstanceCreateInfo},
+    VulkanLibrary,
 };
 use vulkano_util::{
+    context::VulkanoConfig,
     renderer::DeviceImageView,
 };
-use crate::{app::SlimeApp, SENSE_DISTANCE, SENSE_ANGLE, WIDTH, HEIGHT, SCALE, TURN_SPEED, MOVE_SPEED, SENSE_SIZE, DECAY_RATE, DIFFUSE_RATE};
-const NUM_PIXELS: u32 = (WIDTH / SCALE) as u32 * (HEIGHT / SCALE) as u32;
+use crate::profiler::GpuTimer;
+use crate::params::SimParams;
+use crate::scene::{Food, Scene, Sink};
+use crate::animation::animate;
+use crate::stats::{TrailStats, COVERAGE_THRESHOLD, HEADING_BINS, HISTOGRAM_BINS};
+use crate::{app::SlimeApp, AGENT_GROWTH, FRAMES_IN_FLIGHT, SPECIES_COLOURS};
 
 mod slime_shader {
     vulkano_shaders::shader!{
-        ty: "compute",
-        path: "src/slime_moulds.glsl",
+        shaders: {
+            init: {
+                ty: "compute",
+                path: "src/shaders/init.glsl",
+            },
+            spawn: {
+                ty: "compute",
+                path: "src/shaders/spawn.glsl",
+            },
+            prepare: {
+                ty: "compute",
+                path: "src/shaders/prepare.glsl",
+            },
+            emit: {
+                ty: "compute",
+                path: "src/shaders/emit.glsl",
+            },
+            update_fixed: {
+                ty: "compute",
+                path: "src/shaders/update_fixed.glsl",
+            },
+            update_float: {
+                ty: "compute",
+                path: "src/shaders/update_float.glsl",
+            },
+            deposit_fixed: {
+                ty: "compute",
+                path: "src/shaders/deposit_fixed.glsl",
+            },
+            deposit_float: {
+                ty: "compute",
+                path: "src/shaders/deposit_float.glsl",
+            },
+            diffuse_horizontal: {
+                ty: "compute",
+                path: "src/shaders/diffuse_horizontal.glsl",
+            },
+            diffuse_vertical: {
+                ty: "compute",
+                path: "src/shaders/diffuse_vertical.glsl",
+            },
+            trail_stats: {
+                ty: "compute",
+                path: "src/shaders/trail_stats.glsl",
+            },
+            heading_stats: {
+                ty: "compute",
+                path: "src/shaders/heading_stats.glsl",
+            },
+        },
+    }
+}
+
+/// Has to match the format qualifier of `img` and `blur_img` in the shaders.
+const TRAIL_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+
+/// Workgroup sizes, these have to match the `local_size` declared in each shader.
+const PIXEL_GROUP_SIZE: [u32; 2] = [8, 8];
+const BLUR_GROUP_SIZE: [u32; 2] = [16, 16];
+const AGENT_GROUP_SIZE: [u32; 2] = [64, 1];
+/// Has to match the local size and shared arrays in `trail_stats.glsl`.
+const STATS_GROUP_SIZE: [u32; 2] = [16, 16];
+
+/// Largest blur radius whose shared memory tile, `16 * (16 + 2 * radius)` texels of 16 bytes,
+/// fits in the 16KiB every device guarantees.
+pub const MAX_BLUR_RADIUS: i32 = 24;
+
+/// Bytes per agent in the agent buffer.
+const AGENT_SIZE: DeviceSize = std::mem::size_of::<slime_shader::SlimeAgent>() as DeviceSize;
+
+/// Energy every agent spawns with.
+const INITIAL_ENERGY: f32 = 1.0;
+
+/// Has to match `MAX_SPECIES` in `common.glsl`, agents of any higher species share the last colour.
+const MAX_SPECIES: usize = 4;
+
+/// Emitters are rewritten every step with `update_buffer`, which is limited to 64KiB.
+const MAX_EMITTERS: usize = 65536 / std::mem::size_of::<slime_shader::Emitter>();
+
+
+/// Shape of the area each of an agent's three sensors sums the trail over.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SensorKernel {
+    /// Every pixel within `sensor_size` in x and y, the original behaviour.
+    Square = 0,
+    /// Pixels within `sensor_size` of the sensor centre.
+    Circle = 1,
+    /// The square weighted by a gaussian falling off from the centre.
+    Gaussian = 2,
+}
+
+
+/// How agents generate the random numbers they steer with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RngAlgorithm {
+    /// Hash of the agent's pixel and index, agents sharing a pixel get correlated values.
+    Hash = 0,
+    /// A PCG generator per agent.
+    Pcg = 1,
+    /// A xorshift32 generator per agent, cheaper but weaker than PCG.
+    Xorshift = 2,
+}
+
+
+/// How much room the agent buffer makes when appended agents don't fit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AgentGrowth {
+    /// Exactly as many agents as are needed, reallocating on every append that doesn't fit.
+    Exact,
+    /// Double the capacity until the agents fit, so a stream of small appends rarely reallocates.
+    Double,
+}
+
+impl AgentGrowth {
+    fn capacity_for(self, needed: u32, current: u32) -> u32 {
+        let capacity = match self {
+            AgentGrowth::Exact => needed,
+            AgentGrowth::Double => {
+                let mut capacity = current.max(1);
+                while capacity < needed {
+                    capacity = capacity.saturating_mul(2);
+                }
+                capacity
+            }
+        };
+        // vulkan has no empty buffers
+        capacity.max(1)
+    }
+}
+
+
+/// What a pass runs one invocation for, which decides the size of its dispatch.
+#[derive(Clone, Copy)]
+enum PassDomain {
+    Pixels,
+    /// The live agents, only the gpu knows how many there are so it is dispatched indirectly.
+    Agents,
+    /// The agents the host added since the last step.
+    Spawned,
+    /// The agents the emitters add this step.
+    Emitted,
+    Single,
+}
+
+
+/// Picks the device the context will use and turns on float atomics for the trail deposits if it
+/// supports them, otherwise the deposits fall back to fixed point integers.
+///
+/// The context makes its own choice from the config, so the filter is narrowed to the device probed
+/// here to make sure the features are enabled on the device they were checked against.
+pub fn request_device_features(config: &mut VulkanoConfig) {
+    let library = VulkanLibrary::new().unwrap();
+    let instance = Instance::new(
+        library,
+        InstanceCreateInfo {
+            enumerate_portability: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let Some(physical_device) = instance
+        .enumerate_physical_devices()
+        .unwrap()
+        .filter(|p| (config.device_filter_fn)(p))
+        .min_by_key(|p| (config.device_priority_fn)(p))
+    else {
+        // the context fails with its own error
+        return;
+    };
+
+    if physical_device.supported_extensions().ext_shader_atomic_float
+        && physical_device.supported_features().shader_buffer_float32_atomic_add
+    {
+        config.device_extensions.ext_shader_atomic_float = true;
+        config.device_features.shader_buffer_float32_atomic_add = true;
+    }
+
+    let properties = physical_device.properties();
+    let (vendor_id, device_id, device_name) = (properties.vendor_id, properties.device_id, properties.device_name.clone());
+    config.device_filter_fn = Arc::new(move |p| {
+        let properties = p.properties();
+        properties.vendor_id == vendor_id && properties.device_id == device_id && properties.device_name == device_name
+    });
+}
+
+
+/// Every agent gets its own generator state, never zero so xorshift can't get stuck.
+fn seed_agents(rng: &mut StdRng, agents: &[([f32; 2], f32)]) -> Vec<slime_shader::SlimeAgent> {
+    agents
+        .iter()
+        .map(|&(pos, angle)| slime_shader::SlimeAgent {
+            pos: pos.into(),
+            angle: angle.into(),
+            rng: rng.gen_range(1..=u32::MAX),
+            age: 0.0,
+            energy: INITIAL_ENERGY,
+            species: 0,
+            _pad: 0,
+        })
+        .collect()
+}
+
+
+/// Only the gpu touches the agents each frame, so they live in device memory and go through a
+/// staging buffer when the host needs them.
+fn create_agent_buffer(memory_allocator: &StandardMemoryAllocator, capacity: u32) -> Subbuffer<slime_shader::Agents> {
+    Buffer::new_unsized(
+        memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_DST | BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        capacity as DeviceSize,
+    )
+    .unwrap()
+}
+
+
+/// Rewritten every step, so it never needs to be host visible.
+fn create_emitter_buffer(memory_allocator: &StandardMemoryAllocator, len: usize) -> Subbuffer<[slime_shader::Emitter]> {
+    Buffer::new_slice(
+        memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        len.max(1) as DeviceSize,
+    )
+    .unwrap()
+}
+
+
+/// Agents add their trail to this with atomics, then the deposit pass moves it onto the image. Has
+/// a layer of one entry per pixel for each species.
+fn create_deposit_buffer(memory_allocator: &StandardMemoryAllocator, size: [u32; 2], num_species: u32) -> Subbuffer<[u32]> {
+    Buffer::new_slice(
+        memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        size[0] as DeviceSize * size[1] as DeviceSize * num_species as DeviceSize,
+    )
+    .unwrap()
+}
+
+
+fn create_sink_buffer(memory_allocator: &StandardMemoryAllocator, sinks: &[Sink]) -> Subbuffer<[slime_shader::Sink]> {
+    // a buffer can't be empty, num_sinks stops the placeholder being read
+    let placeholder = [Sink { pos: [0.0, 0.0], radius: 0.0 }];
+    let sinks = if sinks.is_empty() { &placeholder[..] } else { sinks };
+    Buffer::from_iter(
+        memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        sinks.iter().map(|sink| slime_shader::Sink {
+            pos: sink.pos,
+            radius: sink.radius,
+            _pad: 0,
+        }),
+    )
+    .unwrap()
+}
+
+
+fn create_food_buffer(memory_allocator: &StandardMemoryAllocator, foods: &[Food]) -> Subbuffer<[slime_shader::Food]> {
+    // a buffer can't be empty, num_foods stops the placeholder being read
+    let placeholder = [Food::default()];
+    let foods = if foods.is_empty() { &placeholder[..] } else { foods };
+    Buffer::from_iter(
+        memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        foods.iter().map(|food| slime_shader::Food {
+            pos: food.pos,
+            radius: food.radius,
+            strength: food.strength,
+        }),
+    )
+    .unwrap()
+}
+
+
+/// The simulation needs more of something than the device allows.
+#[derive(Debug)]
+pub enum LimitError {
+    /// A pass needs more workgroups in some dimension than the device can dispatch at once.
+    Dispatch {
+        pass: &'static str,
+        group_counts: [u32; 3],
+        limit: [u32; 3],
+    },
+    /// More agents than the largest storage buffer the device can bind holds.
+    Agents {
+        count: u64,
+        max: u32,
+    },
+    /// A side of the simulation is longer than the device's images can be.
+    ImageSize {
+        size: [u32; 2],
+        max: u32,
+    },
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::Dispatch { pass, group_counts, limit } => write!(
+                f,
+                "the {pass} pass needs {group_counts:?} workgroups but the device's maxComputeWorkGroupCount is {limit:?}"
+            ),
+            LimitError::Agents { count, max } => write!(
+                f,
+                "{count} agents don't fit in the device's maxStorageBufferRange, which holds at most {max}"
+            ),
+            LimitError::ImageSize { size, max } => write!(
+                f,
+                "a {}x{} simulation is larger than the device's maxImageDimension2D of {max}",
+                size[0], size[1]
+            ),
+        }
+    }
+}
+
+impl Error for LimitError {}
+
+
+/// Host visible copies of the statistics, one per frame in flight so each can be read once the app
+/// has waited for its frame.
+struct StatsReadback {
+    coverage_threshold: f32,
+    counts: Vec<Subbuffer<slime_shader::StatsCounts>>,
+    sums: Vec<Subbuffer<[f32]>>,
+    /// Step each slot's copies were taken at, `None` once they have been read.
+    frames: Vec<Option<u32>>,
+    latest: Option<TrailStats>,
+}
+
+impl StatsReadback {
+    /// Finishes the reduction of the statistics last copied to frame slot `frame`, if the gpu is
+    /// done with them and they're newer than the latest.
+    fn collect(&mut self, frame: usize, image_size: [u32; 2]) {
+        let Some(step) = self.frames[frame] else {
+            return;
+        };
+        if self.latest.as_ref().is_some_and(|latest| latest.frame > step) {
+            self.frames[frame] = None;
+            return;
+        }
+        let (Ok(counts), Ok(sums)) = (self.counts[frame].read(), self.sums[frame].read()) else {
+            return;
+        };
+        let num_pixels = image_size[0] as f64 * image_size[1] as f64;
+        let total: f64 = sums.iter().map(|&sum| sum as f64).sum();
+        self.latest = Some(TrailStats {
+            frame: step,
+            mean: (total / num_pixels) as f32,
+            max: f32::from_bits(counts.max_strength),
+            total: total as f32,
+            coverage: (counts.covered as f64 / num_pixels) as f32,
+            histogram: counts.histogram,
+            headings: counts.headings,
+        });
+        self.frames[frame] = None;
+    }
+}
+
+
+/// One of the simulation's compute shaders and the descriptor sets it runs with.
+///
+/// The descriptor sets are built once here instead of on every dispatch, so they only need
+/// rebuilding if the image or agent buffers are replaced, or the pipeline is respecialised.
+struct ComputePass {
+    name: &'static str,
+    pipeline: Arc<ComputePipeline>,
+    /// One for each of the agent buffers holding the live agents.
+    descriptor_sets: [Arc<PersistentDescriptorSet>; 2],
+    local_size: [u32; 2],
+    domain: PassDomain,
+    /// Only dispatched while the statistics are being read back.
+    stats: bool,
+}
+
+impl ComputePass {
+    /// `writes` gives the descriptor set's contents from the buffer holding the live agents and the
+    /// one the survivors go to.
+    fn new<Css: SpecializationConstants, W: IntoIterator<Item = WriteDescriptorSet>>(
+        descriptor_set_allocator: &StandardDescriptorSetAllocator,
+        queue: &Arc<Queue>,
+        name: &'static str,
+        entry_point: EntryPoint,
+        specialization_constants: &Css,
+        domain: PassDomain,
+        local_size: [u32; 2],
+        agent_buffers: &[Subbuffer<slime_shader::Agents>; 2],
+        writes: impl Fn(&Subbuffer<slime_shader::Agents>, &Subbuffer<slime_shader::Agents>) -> W,
+    ) -> ComputePass {
+        let pipeline = ComputePipeline::new(
+            queue.device().clone(),
+            entry_point,
+            specialization_constants,
+            None,
+            |_| {},
+        )
+        .unwrap();
+
+        let desc_layout = pipeline.layout().set_layouts().get(0).unwrap();
+        let descriptor_sets = [0, 1].map(|live| {
+            PersistentDescriptorSet::new(
+                descriptor_set_allocator,
+                desc_layout.clone(),
+                writes(&agent_buffers[live], &agent_buffers[1 - live]),
+            )
+            .unwrap()
+        });
+
+        ComputePass {
+            name,
+            pipeline,
+            descriptor_sets,
+            local_size,
+            domain,
+            stats: false,
+        }
+    }
+
+    fn group_counts(
+        &self,
+        image_size: [u32; 2],
+        num_agents: u32,
+        limit: [u32; 3],
+    ) -> Result<[u32; 3], LimitError> {
+        let group_counts = match self.domain {
+            PassDomain::Pixels => [
+                image_size[0].div_ceil(self.local_size[0]),
+                image_size[1].div_ceil(self.local_size[1]),
+                1,
+            ],
+            // agents are spread over rows of at most limit[0] groups, the shader flattens the
+            // invocation id back into an agent index
+            PassDomain::Agents | PassDomain::Spawned | PassDomain::Emitted => {
+                let groups = num_agents.div_ceil(self.local_size[0]);
+                let row = groups.clamp(1, limit[0]);
+                [row, groups.div_ceil(row), 1]
+            }
+            PassDomain::Single => [1, 1, 1],
+        };
+
+        if group_counts.iter().zip(limit).any(|(&count, max)| count > max) {
+            return Err(LimitError::Dispatch {
+                pass: self.name,
+                group_counts,
+                limit,
+            });
+        }
+        Ok(group_counts)
     }
 }
 
 
 pub struct SlimeComputePipeline {
     compute_queue: Arc<Queue>,
-    compute_pipeline: Arc<ComputePipeline>,
+    /// The compute queue is in a different family to the one rendering, so its results reach the
+    /// render pass through a semaphore.
+    separate_queue: bool,
+    /// Fence after the most recent submission, every submission follows on from it so the gpu
+    /// knows the simulation resources it is still using.
+    last_submission: Option<Arc<FenceSignalFuture<Box<dyn GpuFuture>>>>,
     command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
     descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
-    // life_in: Subbuffer<shader::ty>,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    /// Clears the image, only run by `init`.
+    init_pass: ComputePass,
+    /// Run in order every step.
+    passes: Vec<ComputePass>,
     image: DeviceImageView,
-    agent_buffer: Subbuffer<slime_shader::Agents>,
-    // agent_buffer: Subbuffer<[[f32; 2]]>,
+    blur_image: DeviceImageView,
+    /// Copies of the trail the render pass reads, one per frame in flight, so the next step can
+    /// change the trail while earlier frames are still being drawn.
+    display_images: Vec<DeviceImageView>,
+    image_size: [u32; 2],
+    max_group_counts: [u32; 3],
+    /// Most agents an agent buffer can hold and still be bound as a storage buffer.
+    max_agents: u32,
+    /// Each holds `agent_capacity` agents. The live ones are packed at the start of
+    /// `agent_buffers[live_buffer]`, and steps where agents can die or split write the survivors and
+    /// offspring to the other one, which takes over for the next step.
+    agent_buffers: [Subbuffer<slime_shader::Agents>; 2],
+    live_buffer: usize,
+    agent_capacity: u32,
+    /// How many agents are alive, only ever changed on the gpu.
+    agent_counts: Subbuffer<slime_shader::AgentCounts>,
+    /// Workgroup counts for the agent update, written by the prepare pass.
+    dispatch_args: Subbuffer<[DispatchIndirectCommand]>,
+    /// The live count copied out at the end of each step, one per frame in flight so it can be
+    /// read once the app has waited for that frame.
+    count_readback: Vec<Subbuffer<u32>>,
+    /// Slots whose readback was copied out before the agents were last cleared, or never written.
+    stale_counts: Vec<bool>,
+    /// Agents added by the host, appended to the agent buffer by the spawn pass.
+    spawn_buffer: Subbuffer<slime_shader::Agents>,
+    spawn_capacity: u32,
+    /// Agents waiting for the next step to upload them.
+    pending: Vec<slime_shader::SlimeAgent>,
+    num_spawned: u32,
+    /// Zero the live count at the start of the next step.
+    reset_counts: bool,
+    /// Cut the population down to this many at the start of the next step.
+    max_alive: Option<u32>,
+    scene: Scene,
+    /// Fractions of an agent each emitter has left over from earlier steps.
+    emitter_carry: Vec<f32>,
+    emitter_buffer: Subbuffer<[slime_shader::Emitter]>,
+    sink_buffer: Subbuffer<[slime_shader::Sink]>,
+    food_buffer: Subbuffer<[slime_shader::Food]>,
+    num_emitted: u32,
+    /// Seeds the positions, headings and generator states of this step's emitted agents.
+    emit_seed: u32,
+    /// What the stats passes add up, zeroed at the start of every step they run in.
+    stats_counts: Subbuffer<slime_shader::StatsCounts>,
+    /// The trail strength summed over each workgroup of the trail stats pass.
+    stats_sums: Subbuffer<[f32]>,
+    /// Set while the stats passes are running.
+    stats: Option<StatsReadback>,
+    growth: AgentGrowth,
+    /// Generator states for agents added after creation, and the emitters' seeds.
+    rng: StdRng,
+    deposit_buffer: Subbuffer<[u32]>,
+    /// Species the deposit buffer has layers for, one more than the highest any emitter spawns.
+    num_species: u32,
+    /// Zero the deposits at the start of the next step, after the buffer was replaced.
+    clear_deposits: bool,
+    /// `params` as the shaders see them, rewritten at the start of the next step after a change.
+    settings_buffer: Subbuffer<slime_shader::SimSettings>,
+    settings_dirty: bool,
+    params: SimParams,
+    /// Live agents as of the last count read back, a few frames behind the gpu.
     num_agents: u32,
+    delta_time: f32,
+    frame: u32,
+    timer: Option<GpuTimer>,
 }
 
 
@@ -41,161 +602,1187 @@ impl SlimeComputePipeline {
         compute_queue: Arc<Queue>,
         size: [u32; 2],
         agent_data: Vec<([f32; 2], f32)>,
-    ) -> Self {
+        params: SimParams,
+    ) -> Result<Self, LimitError> {
         let memory_allocator = app.context.memory_allocator();
+        let device = compute_queue.device().clone();
+        let limits = device.physical_device().properties();
+        let max_agents = (limits.max_storage_buffer_range as DeviceSize / AGENT_SIZE) as u32;
 
-        let pipeline = {
-            let shader = slime_shader::load(compute_queue.device().clone()).unwrap();
-            ComputePipeline::new(
-                compute_queue.device().clone(),
-                shader.entry_point("main").unwrap(),
-                &(),
-                None,
-                |_| {},
-            )
-            .unwrap()
-        };
+        let max_image_size = limits.max_image_dimension2_d;
+        if size[0] > max_image_size || size[1] > max_image_size {
+            return Err(LimitError::ImageSize { size, max: max_image_size });
+        }
 
         let image = StorageImage::general_purpose_image_view(
             memory_allocator,
             compute_queue.clone(),
             size,
-            Format::R8G8B8A8_UNORM,
-            ImageUsage::SAMPLED | ImageUsage::STORAGE | ImageUsage::TRANSFER_DST,
+            TRAIL_FORMAT,
+            ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC,
         )
         .unwrap();
 
-        // let agent_data = vec![([0.0, 0.0], 1.5), ([0.0, 0.0], 0.0)];
-        let mut agent_buffer_data: Vec<Padded<slime_shader::SlimeAgent, 4>> = Vec::new();
-        for (pos, angle) in agent_data {
-            agent_buffer_data.push(Padded(slime_shader::SlimeAgent{pos: pos.into(), angle: angle.into()}));
+        // the display images are the only thing the render queue touches, shared concurrently
+        // between the two families when compute has its own
+        let render_family = app.context.graphics_queue().queue_family_index();
+        let separate_queue = compute_queue.queue_family_index() != render_family;
+        let mut queue_families = vec![compute_queue.queue_family_index()];
+        if separate_queue {
+            queue_families.push(render_family);
         }
-        let num_agents = agent_buffer_data.len() as u32;
+        info!(
+            "compute runs on queue family {}{}",
+            compute_queue.queue_family_index(),
+            if separate_queue {", separate from rendering"} else {""}
+        );
+
+        let display_images = (0..FRAMES_IN_FLIGHT)
+            .map(|_| {
+                let image = StorageImage::with_usage(
+                    memory_allocator,
+                    ImageDimensions::Dim2d {
+                        width: size[0],
+                        height: size[1],
+                        array_layers: 1,
+                    },
+                    TRAIL_FORMAT,
+                    ImageUsage::SAMPLED | ImageUsage::TRANSFER_DST,
+                    ImageCreateFlags::empty(),
+                    queue_families.iter().copied(),
+                )
+                .unwrap();
+                ImageView::new_default(image).unwrap()
+            })
+            .collect();
+
+        // intermediate for the separable blur, never leaves the gpu
+        let blur_image = StorageImage::general_purpose_image_view(
+            memory_allocator,
+            compute_queue.clone(),
+            size,
+            TRAIL_FORMAT,
+            ImageUsage::STORAGE,
+        )
+        .unwrap();
+
+        // let agent_data = vec![([0.0, 0.0], 1.5), ([0.0, 0.0], 0.0)];
+        // offset so the generator states aren't drawn from the stream that placed the agents
+        let mut rng = StdRng::seed_from_u64(app.seed.wrapping_add(3));
+        let agent_buffer_data = seed_agents(&mut rng, &agent_data);
+        let num_agents = u32::try_from(agent_buffer_data.len())
+            .ok()
+            .filter(|&count| count <= max_agents)
+            .ok_or(LimitError::Agents { count: agent_buffer_data.len() as u64, max: max_agents })?;
+
+        // the starting agents go through the spawn pass like any others
+        let agent_capacity = AgentGrowth::Exact.capacity_for(num_agents, 0);
+        let agent_buffers = [(); 2].map(|_| create_agent_buffer(memory_allocator, agent_capacity));
+        let spawn_buffer = create_agent_buffer(memory_allocator, agent_capacity);
 
-        let agent_buffer: Subbuffer<slime_shader::Agents> = Buffer::new_unsized(
-            app.context.memory_allocator(),
+        let agent_counts: Subbuffer<slime_shader::AgentCounts> = Buffer::new_sized(
+            memory_allocator,
             BufferCreateInfo {
-                usage: BufferUsage::STORAGE_BUFFER,
+                usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST,
                 ..Default::default()
             },
             AllocationCreateInfo {
-                usage: MemoryUsage::Upload,
+                usage: MemoryUsage::DeviceOnly,
                 ..Default::default()
             },
-            num_agents as DeviceSize
-        ).unwrap();
+        )
+        .unwrap();
+        let dispatch_args: Subbuffer<[DispatchIndirectCommand]> = Buffer::new_slice(
+            memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER | BufferUsage::INDIRECT_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::DeviceOnly,
+                ..Default::default()
+            },
+            1,
+        )
+        .unwrap();
+        let count_readback = (0..FRAMES_IN_FLIGHT)
+            .map(|_| {
+                Buffer::from_data(
+                    memory_allocator,
+                    BufferCreateInfo {
+                        usage: BufferUsage::TRANSFER_DST,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        usage: MemoryUsage::Download,
+                        ..Default::default()
+                    },
+                    num_agents,
+                )
+                .unwrap()
+            })
+            .collect();
+        info!(
+            "simulation image {}x{} {:?}, {num_agents} agents ({} bytes)",
+            size[0], size[1], TRAIL_FORMAT, agent_buffers[0].size()
+        );
 
-        agent_buffer.write().unwrap().agents.copy_from_slice(agent_buffer_data.as_slice());
+        let init_shader = slime_shader::load_init(device.clone()).unwrap();
+        let init_pass = ComputePass::new(
+            &app.descriptor_set_allocator,
+            &compute_queue,
+            "init",
+            init_shader.entry_point("main").unwrap(),
+            &(),
+            PassDomain::Pixels,
+            PIXEL_GROUP_SIZE,
+            &agent_buffers,
+            |_, _| [WriteDescriptorSet::image_view(0, image.clone())],
+        );
 
+        // the starting agents are all the first species
+        let deposit_buffer = create_deposit_buffer(memory_allocator, size, 1);
 
-        SlimeComputePipeline {
-            compute_queue: compute_queue,
-            compute_pipeline: pipeline,
+        let stats_counts: Subbuffer<slime_shader::StatsCounts> = Buffer::new_sized(
+            memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::DeviceOnly,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let stats_sums: Subbuffer<[f32]> = Buffer::new_slice(
+            memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::DeviceOnly,
+                ..Default::default()
+            },
+            size[0].div_ceil(STATS_GROUP_SIZE[0]) as DeviceSize * size[1].div_ceil(STATS_GROUP_SIZE[1]) as DeviceSize,
+        )
+        .unwrap();
+
+        let float_atomics = device.enabled_features().shader_buffer_float32_atomic_add;
+        info!("trail deposits use {} atomics", if float_atomics {"float"} else {"fixed point"});
+
+        // small and rarely written, so it's filled with update_buffer rather than a staging copy
+        let settings_buffer: Subbuffer<slime_shader::SimSettings> = Buffer::new_sized(
+            memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::UNIFORM_BUFFER | BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::DeviceOnly,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+
+        let mut slime_pipeline = SlimeComputePipeline {
+            compute_queue: compute_queue.clone(),
+            separate_queue,
+            last_submission: None,
             command_buffer_allocator: app.command_buffer_allocator.clone(),
             descriptor_set_allocator: app.descriptor_set_allocator.clone(),
+            memory_allocator: memory_allocator.clone(),
+            init_pass,
+            passes: Vec::new(),
             image,
-            agent_buffer,
-            num_agents
+            blur_image,
+            display_images,
+            image_size: size,
+            max_group_counts: limits.max_compute_work_group_count,
+            max_agents,
+            agent_buffers,
+            live_buffer: 0,
+            agent_capacity,
+            agent_counts,
+            dispatch_args,
+            count_readback,
+            stale_counts: vec![true; FRAMES_IN_FLIGHT],
+            spawn_buffer,
+            spawn_capacity: agent_capacity,
+            pending: agent_buffer_data,
+            num_spawned: 0,
+            reset_counts: true,
+            max_alive: None,
+            scene: Scene::default(),
+            emitter_carry: Vec::new(),
+            emitter_buffer: create_emitter_buffer(memory_allocator, 0),
+            sink_buffer: create_sink_buffer(memory_allocator, &[]),
+            food_buffer: create_food_buffer(memory_allocator, &[]),
+            num_emitted: 0,
+            emit_seed: 0,
+            stats_counts,
+            stats_sums,
+            stats: None,
+            growth: AGENT_GROWTH,
+            rng,
+            deposit_buffer,
+            num_species: 1,
+            clear_deposits: false,
+            settings_buffer,
+            settings_dirty: true,
+            params,
+            num_agents: 0,
+            delta_time: 0.0,
+            frame: 0,
+            timer: None,
+        };
+        slime_pipeline.passes = slime_pipeline.build_passes();
+
+        if app.profile {
+            let names: Vec<&'static str> = slime_pipeline.passes.iter().map(|pass| pass.name).collect();
+            slime_pipeline.timer = GpuTimer::new(&compute_queue, &names, FRAMES_IN_FLIGHT);
+        }
+
+        for pass in slime_pipeline.passes.iter().chain([&slime_pipeline.init_pass]) {
+            pass.group_counts(size, agent_capacity, slime_pipeline.max_group_counts)?;
         }
 
+        Ok(slime_pipeline)
+
     }
 
-    pub fn colour_image(&self) -> DeviceImageView {
-        self.image.clone()
+    /// Creates the per step passes for the current parameters' specialisation constants.
+    fn build_passes(&self) -> Vec<ComputePass> {
+        let device = self.compute_queue.device().clone();
+        let descriptor_set_allocator = &self.descriptor_set_allocator;
+        let compute_queue = &self.compute_queue;
+        let params = &self.params;
+        let agent_buffers = &self.agent_buffers;
+
+        let spawn_shader = slime_shader::load_spawn(device.clone()).unwrap();
+        let spawn_pass = ComputePass::new(
+            descriptor_set_allocator,
+            compute_queue,
+            "spawn",
+            spawn_shader.entry_point("main").unwrap(),
+            &(),
+            PassDomain::Spawned,
+            AGENT_GROUP_SIZE,
+            agent_buffers,
+            |agents, _| [
+                WriteDescriptorSet::buffer(1, agents.clone()),
+                WriteDescriptorSet::buffer(6, self.agent_counts.clone()),
+                WriteDescriptorSet::buffer(8, self.spawn_buffer.clone()),
+            ],
+        );
+
+        let prepare_shader = slime_shader::load_prepare(device.clone()).unwrap();
+        let prepare_pass = ComputePass::new(
+            descriptor_set_allocator,
+            compute_queue,
+            "prepare",
+            prepare_shader.entry_point("main").unwrap(),
+            &slime_shader::PrepareSpecializationConstants {
+                max_groups_x: self.max_group_counts[0],
+            },
+            PassDomain::Single,
+            [1, 1],
+            agent_buffers,
+            |_, _| [
+                WriteDescriptorSet::buffer(6, self.agent_counts.clone()),
+                WriteDescriptorSet::buffer(7, self.dispatch_args.clone()),
+            ],
+        );
+
+        let heading_stats_shader = slime_shader::load_heading_stats(device.clone()).unwrap();
+        let mut heading_stats_pass = ComputePass::new(
+            descriptor_set_allocator,
+            compute_queue,
+            "heading stats",
+            heading_stats_shader.entry_point("main").unwrap(),
+            &(),
+            PassDomain::Agents,
+            AGENT_GROUP_SIZE,
+            agent_buffers,
+            |agents, _| [
+                WriteDescriptorSet::buffer(1, agents.clone()),
+                WriteDescriptorSet::buffer(6, self.agent_counts.clone()),
+                WriteDescriptorSet::buffer(11, self.stats_counts.clone()),
+            ],
+        );
+        heading_stats_pass.stats = true;
+
+        let emit_shader = slime_shader::load_emit(device.clone()).unwrap();
+        let emit_pass = ComputePass::new(
+            descriptor_set_allocator,
+            compute_queue,
+            "emit",
+            emit_shader.entry_point("main").unwrap(),
+            &(),
+            PassDomain::Emitted,
+            AGENT_GROUP_SIZE,
+            agent_buffers,
+            |agents, _| [
+                WriteDescriptorSet::buffer(1, agents.clone()),
+                WriteDescriptorSet::buffer(4, self.settings_buffer.clone()),
+                WriteDescriptorSet::buffer(6, self.agent_counts.clone()),
+                WriteDescriptorSet::buffer(9, self.emitter_buffer.clone()),
+            ],
+        );
+
+        let update_writes = |agents: &Subbuffer<slime_shader::Agents>, agents_out: &Subbuffer<slime_shader::Agents>| [
+            WriteDescriptorSet::image_view(0, self.image.clone()),
+            WriteDescriptorSet::buffer(1, agents.clone()),
+            WriteDescriptorSet::buffer(3, self.deposit_buffer.clone()),
+            WriteDescriptorSet::buffer(4, self.settings_buffer.clone()),
+            WriteDescriptorSet::buffer(5, agents_out.clone()),
+            WriteDescriptorSet::buffer(6, self.agent_counts.clone()),
+            WriteDescriptorSet::buffer(10, self.sink_buffer.clone()),
+        ];
+        let deposit_writes = |_: &Subbuffer<slime_shader::Agents>, _: &Subbuffer<slime_shader::Agents>| [
+            WriteDescriptorSet::image_view(0, self.image.clone()),
+            WriteDescriptorSet::buffer(3, self.deposit_buffer.clone()),
+            WriteDescriptorSet::buffer(4, self.settings_buffer.clone()),
+            WriteDescriptorSet::buffer(13, self.food_buffer.clone()),
+        ];
+        let float_atomics = device.enabled_features().shader_buffer_float32_atomic_add;
+
+        let (update_pass, deposit_pass) = if float_atomics {
+            let update_shader = slime_shader::load_update_float(device.clone()).unwrap();
+            let deposit_shader = slime_shader::load_deposit_float(device.clone()).unwrap();
+            (
+                ComputePass::new(
+                    descriptor_set_allocator,
+                    compute_queue,
+                    "agent update",
+                    update_shader.entry_point("main").unwrap(),
+                    &slime_shader::UpdateFloatSpecializationConstants {
+                        sensor_size: params.sensor_size,
+                        sensor_kernel: params.sensor_kernel as i32,
+                        sub_pixel: params.sub_pixel as u32,
+                        rng_algorithm: params.rng_algorithm as i32,
+                    },
+                    PassDomain::Agents,
+                    AGENT_GROUP_SIZE,
+                    agent_buffers,
+                    update_writes,
+                ),
+                ComputePass::new(
+                    descriptor_set_allocator,
+                    compute_queue,
+                    "deposit",
+                    deposit_shader.entry_point("main").unwrap(),
+                    &(),
+                    PassDomain::Pixels,
+                    PIXEL_GROUP_SIZE,
+                    agent_buffers,
+                    deposit_writes,
+                ),
+            )
+        } else {
+            let update_shader = slime_shader::load_update_fixed(device.clone()).unwrap();
+            let deposit_shader = slime_shader::load_deposit_fixed(device.clone()).unwrap();
+            (
+                ComputePass::new(
+                    descriptor_set_allocator,
+                    compute_queue,
+                    "agent update",
+                    update_shader.entry_point("main").unwrap(),
+                    &slime_shader::UpdateFixedSpecializationConstants {
+                        sensor_size: params.sensor_size,
+                        sensor_kernel: params.sensor_kernel as i32,
+                        sub_pixel: params.sub_pixel as u32,
+                        rng_algorithm: params.rng_algorithm as i32,
+                    },
+                    PassDomain::Agents,
+                    AGENT_GROUP_SIZE,
+                    agent_buffers,
+                    update_writes,
+                ),
+                ComputePass::new(
+                    descriptor_set_allocator,
+                    compute_queue,
+                    "deposit",
+                    deposit_shader.entry_point("main").unwrap(),
+                    &(),
+                    PassDomain::Pixels,
+                    PIXEL_GROUP_SIZE,
+                    agent_buffers,
+                    deposit_writes,
+                ),
+            )
+        };
+
+        assert!(
+            (0..=MAX_BLUR_RADIUS).contains(&params.blur_radius),
+            "blur radius must be between 0 and {MAX_BLUR_RADIUS}, got {}",
+            params.blur_radius
+        );
+
+        let diffuse_horizontal_shader = slime_shader::load_diffuse_horizontal(device.clone()).unwrap();
+        let diffuse_horizontal_pass = ComputePass::new(
+            descriptor_set_allocator,
+            compute_queue,
+            "diffuse horizontal",
+            diffuse_horizontal_shader.entry_point("main").unwrap(),
+            &slime_shader::DiffuseHorizontalSpecializationConstants {
+                blur_radius: params.blur_radius,
+            },
+            PassDomain::Pixels,
+            BLUR_GROUP_SIZE,
+            agent_buffers,
+            |_, _| [
+                WriteDescriptorSet::image_view(0, self.image.clone()),
+                WriteDescriptorSet::image_view(2, self.blur_image.clone()),
+                WriteDescriptorSet::buffer(4, self.settings_buffer.clone()),
+            ],
+        );
+
+        let diffuse_vertical_shader = slime_shader::load_diffuse_vertical(device.clone()).unwrap();
+        let diffuse_vertical_pass = ComputePass::new(
+            descriptor_set_allocator,
+            compute_queue,
+            "diffuse vertical",
+            diffuse_vertical_shader.entry_point("main").unwrap(),
+            &slime_shader::DiffuseVerticalSpecializationConstants {
+                blur_radius: params.blur_radius,
+            },
+            PassDomain::Pixels,
+            BLUR_GROUP_SIZE,
+            agent_buffers,
+            |_, _| [
+                WriteDescriptorSet::image_view(0, self.image.clone()),
+                WriteDescriptorSet::image_view(2, self.blur_image.clone()),
+                WriteDescriptorSet::buffer(4, self.settings_buffer.clone()),
+            ],
+        );
+
+        let trail_stats_shader = slime_shader::load_trail_stats(device.clone()).unwrap();
+        let mut trail_stats_pass = ComputePass::new(
+            descriptor_set_allocator,
+            compute_queue,
+            "trail stats",
+            trail_stats_shader.entry_point("main").unwrap(),
+            &slime_shader::TrailStatsSpecializationConstants {
+                coverage_threshold: self.stats.as_ref().map_or(COVERAGE_THRESHOLD, |stats| stats.coverage_threshold),
+            },
+            PassDomain::Pixels,
+            STATS_GROUP_SIZE,
+            agent_buffers,
+            |_, _| [
+                WriteDescriptorSet::image_view(0, self.image.clone()),
+                WriteDescriptorSet::buffer(11, self.stats_counts.clone()),
+                WriteDescriptorSet::buffer(12, self.stats_sums.clone()),
+            ],
+        );
+        trail_stats_pass.stats = true;
+
+        // the agents' headings are counted going into the update, while the indirect dispatch
+        // still matches them, and the trail once the step is done with it
+        vec![
+            spawn_pass,
+            emit_pass,
+            prepare_pass,
+            heading_stats_pass,
+            update_pass,
+            deposit_pass,
+            diffuse_horizontal_pass,
+            diffuse_vertical_pass,
+            trail_stats_pass,
+        ]
+    }
+
+    pub fn params(&self) -> &SimParams {
+        &self.params
+    }
+
+    /// Replaces the simulation parameters. The settings buffer is rewritten at the start of the
+    /// next step, and the pipelines are only rebuilt if a specialisation constant changed.
+    pub fn set_params(&mut self, params: SimParams) {
+        if params == self.params {
+            return;
+        }
+        let rebuild = params.needs_rebuild(&self.params);
+        self.params = params;
+        self.settings_dirty = true;
+        if rebuild {
+            debug!("specialisation constants changed, rebuilding compute pipelines");
+            self.passes = self.build_passes();
+        }
+    }
+
+    /// Replaces the emitters, sinks and food, taking effect from the next step.
+    pub fn set_scene(&mut self, mut scene: Scene) {
+        if scene.emitters.len() > MAX_EMITTERS {
+            warn!("scene has {} emitters, only the first {MAX_EMITTERS} are used", scene.emitters.len());
+            scene.emitters.truncate(MAX_EMITTERS);
+        }
+        info!(
+            "scene with {} emitters, {} sinks, {} food sources and {} animated parameters",
+            scene.emitters.len(), scene.sinks.len(), scene.foods.len(), scene.tracks.len()
+        );
+        if scene.emitters.len() != self.scene.emitters.len() {
+            self.emitter_buffer = create_emitter_buffer(&self.memory_allocator, scene.emitters.len());
+        }
+        let highest_species = scene.emitters.iter().map(|emitter| emitter.species).max().unwrap_or(0);
+        if highest_species >= MAX_SPECIES as u32 {
+            warn!("species {highest_species} is past the {MAX_SPECIES} with their own trail colour, it shares the last one's");
+        }
+        let num_species = (highest_species + 1).min(MAX_SPECIES as u32);
+        if num_species != self.num_species {
+            self.deposit_buffer = create_deposit_buffer(&self.memory_allocator, self.image_size, num_species);
+            self.num_species = num_species;
+            self.clear_deposits = true;
+        }
+        self.emitter_carry = vec![0.0; scene.emitters.len()];
+        self.sink_buffer = create_sink_buffer(&self.memory_allocator, &scene.sinks);
+        self.food_buffer = create_food_buffer(&self.memory_allocator, &scene.foods);
+        self.scene = scene;
+        self.settings_dirty = true;
+        self.passes = self.build_passes();
+    }
+
+    /// Steps since the simulation was created.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Live agents, read back from the gpu so a few frames out of date while agents die and split.
+    pub fn agent_count(&self) -> u32 {
+        self.num_agents
+    }
+
+    /// Agents the buffer holds before it has to be reallocated.
+    pub fn agent_capacity(&self) -> u32 {
+        self.agent_capacity
+    }
+
+    /// Most agents the device can bind in one buffer.
+    pub fn max_agents(&self) -> u32 {
+        self.max_agents
+    }
+
+    /// Runs the stats passes every step from now on, counting trail pixels at least
+    /// `coverage_threshold` strong as covered. The results come back through [`Self::trail_stats`].
+    pub fn enable_stats(&mut self, coverage_threshold: f32) {
+        let download = BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        };
+        let host_visible = AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        };
+        self.stats = Some(StatsReadback {
+            coverage_threshold,
+            counts: (0..FRAMES_IN_FLIGHT)
+                .map(|_| Buffer::new_sized(&self.memory_allocator, download.clone(), host_visible.clone()).unwrap())
+                .collect(),
+            sums: (0..FRAMES_IN_FLIGHT)
+                .map(|_| {
+                    Buffer::new_slice(&self.memory_allocator, download.clone(), host_visible.clone(), self.stats_sums.len())
+                        .unwrap()
+                })
+                .collect(),
+            frames: vec![None; FRAMES_IN_FLIGHT],
+            latest: None,
+        });
+        // the threshold is a specialisation constant
+        self.passes = self.build_passes();
+        info!("trail statistics on, coverage threshold {coverage_threshold}");
+    }
+
+    /// Statistics from the newest step the host has seen finish, so a few steps behind the gpu.
+    /// `None` until stats are enabled and the first step with them has come back.
+    pub fn trail_stats(&self) -> Option<&TrailStats> {
+        self.stats.as_ref().and_then(|stats| stats.latest.as_ref())
+    }
+
+    /// Blocks until every step submitted so far has finished, then reads back the statistics of
+    /// the last one. `None` if stats aren't enabled or no step has run with them.
+    pub fn finish_stats(&mut self) -> Option<&TrailStats> {
+        self.wait();
+        let stats = self.stats.as_mut()?;
+        for frame in 0..FRAMES_IN_FLIGHT {
+            stats.collect(frame, self.image_size);
+        }
+        stats.latest.as_ref()
+    }
+
+    /// Queues agents to be added by the next step, which grows the buffer by the growth policy if
+    /// they don't fit.
+    pub fn append_agents(&mut self, agents: &[([f32; 2], f32)]) -> Result<(), LimitError> {
+        if agents.is_empty() {
+            return Ok(());
+        }
+        let added = self.pending.len() + agents.len();
+        let count = u32::try_from(added)
+            .ok()
+            .and_then(|added| self.num_agents.checked_add(added))
+            .ok_or(LimitError::Agents { count: self.num_agents as u64 + added as u64, max: self.max_agents })?;
+        self.check_agent_count(count)?;
+
+        let new_agents = seed_agents(&mut self.rng, agents);
+        self.pending.extend(new_agents);
+        debug!("queued {} agents, {} pending", agents.len(), self.pending.len());
+        Ok(())
+    }
+
+    /// Removes every agent, the buffer keeps its capacity.
+    pub fn clear_agents(&mut self) {
+        self.pending.clear();
+        self.reset_counts = true;
+        // the steps still in flight copy out counts from before the clear
+        self.stale_counts.fill(true);
+        self.num_agents = 0;
+        debug!("cleared agents");
     }
 
-    pub fn init(
+    /// Truncates the population to `count` at the next step, or grows it with as many agents as
+    /// `spawn` is asked for. Goes by the last count read back, so with reproduction on the result
+    /// is only approximate.
+    pub fn resize_agents(
         &mut self,
-        before_future: Box<dyn GpuFuture>
-    ) -> Box<dyn GpuFuture> {
+        count: u32,
+        spawn: impl FnOnce(usize) -> Vec<([f32; 2], f32)>,
+    ) -> Result<(), LimitError> {
+        self.check_agent_count(count)?;
+        let queued = u32::try_from(self.pending.len())
+            .ok()
+            .and_then(|pending| self.num_agents.checked_add(pending))
+            .unwrap_or(u32::MAX);
+        if count <= queued {
+            self.max_alive = Some(count);
+            return Ok(());
+        }
+        self.append_agents(&spawn((count - queued) as usize))
+    }
+
+    fn check_agent_count(&self, count: u32) -> Result<(), LimitError> {
+        if count > self.max_agents {
+            return Err(LimitError::Agents { count: count as u64, max: self.max_agents });
+        }
+        for pass in &self.passes {
+            pass.group_counts(self.image_size, count, self.max_group_counts)?;
+        }
+        Ok(())
+    }
+
+    /// Reallocates the agent buffers if they can't hold `count` agents, copying the old contents
+    /// across in `builder` and pointing the passes at the new buffers. The live count is only known
+    /// on the gpu so the whole buffer is copied.
+    fn reserve_agents(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer, Arc<StandardCommandBufferAllocator>>,
+        count: u32,
+    ) {
+        if count <= self.agent_capacity || self.agent_capacity >= self.max_agents {
+            return;
+        }
+        let capacity = self.growth.capacity_for(count, self.agent_capacity).min(self.max_agents);
+        let agent_buffer = create_agent_buffer(&self.memory_allocator, capacity);
+        info!("agent buffer grown from {} to {capacity} agents ({} bytes)", self.agent_capacity, agent_buffer.size());
+
+        builder
+            .copy_buffer(CopyBufferInfo {
+                regions: [BufferCopy {
+                    size: self.agent_capacity as DeviceSize * AGENT_SIZE,
+                    ..Default::default()
+                }]
+                .into(),
+                ..CopyBufferInfo::buffers(self.agent_buffers[self.live_buffer].clone(), agent_buffer.clone())
+            })
+            .unwrap();
+
+        self.agent_buffers = [agent_buffer, create_agent_buffer(&self.memory_allocator, capacity)];
+        self.live_buffer = 0;
+        self.agent_capacity = capacity;
+        self.passes = self.build_passes();
+    }
+
+    /// Copies the pending agents into the spawn buffer through a staging buffer, growing it if
+    /// needed. Returns how many the spawn pass has to append.
+    fn upload_pending(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer, Arc<StandardCommandBufferAllocator>>,
+    ) -> u32 {
+        if self.pending.is_empty() {
+            return 0;
+        }
+        let count = self.pending.len() as u32;
+        if count > self.spawn_capacity {
+            self.spawn_capacity = self.growth.capacity_for(count, self.spawn_capacity).min(self.max_agents);
+            self.spawn_buffer = create_agent_buffer(&self.memory_allocator, self.spawn_capacity);
+            self.passes = self.build_passes();
+        }
+
+        let staging = Buffer::from_iter(
+            &self.memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            self.pending.drain(..),
+        )
+        .unwrap();
+        builder
+            .copy_buffer(CopyBufferInfo {
+                regions: [BufferCopy {
+                    size: count as DeviceSize * AGENT_SIZE,
+                    ..Default::default()
+                }]
+                .into(),
+                ..CopyBufferInfo::buffers(staging, self.spawn_buffer.clone())
+            })
+            .unwrap();
+        count
+    }
+
+    /// Reads every live agent's position and angle back from the gpu, blocking until any work
+    /// already submitted to the compute queue has finished. Agents still pending aren't included.
+    pub fn download_agents(&mut self) -> Vec<([f32; 2], f32)> {
+        let download = BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        };
+        let host_visible = AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        };
+        let counts: Subbuffer<slime_shader::AgentCounts> =
+            Buffer::new_sized(&self.memory_allocator, download.clone(), host_visible.clone()).unwrap();
+        let staging: Subbuffer<[slime_shader::SlimeAgent]> = Buffer::new_slice(
+            &self.memory_allocator,
+            download,
+            host_visible,
+            self.agent_capacity as DeviceSize,
+        )
+        .unwrap();
+
         let mut builder = AutoCommandBufferBuilder::primary(
             &self.command_buffer_allocator,
             self.compute_queue.queue_family_index(),
             CommandBufferUsage::OneTimeSubmit,
         ).unwrap();
+        builder
+            .copy_buffer(CopyBufferInfo::buffers(self.agent_counts.clone(), counts.clone()))
+            .unwrap()
+            .copy_buffer(CopyBufferInfo::buffers(self.agent_buffers[self.live_buffer].clone(), staging.clone()))
+            .unwrap();
+        self.submit_and_wait(builder);
 
+        // counts are only zeroed at the start of the next step
+        let alive = if self.reset_counts { 0 } else { counts.read().unwrap().alive.min(self.agent_capacity) };
+        self.num_agents = alive;
+        let agents = staging.read().unwrap();
+        agents[..alive as usize].iter().map(|agent| (agent.pos, agent.angle)).collect()
+    }
 
-        self.dispatch(&mut builder, 0, ((NUM_PIXELS - 1) / 64 as u32) * 64 + 64);
+    /// Reads the trail back from the gpu, row by row, as the deposited colour with the strength the
+    /// agents sense in alpha. Blocks until any work already submitted to the compute queue has
+    /// finished.
+    pub fn download_trail(&mut self) -> Vec<[f32; 4]> {
+        let staging: Subbuffer<[[u16; 4]]> = Buffer::new_slice(
+            &self.memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Download,
+                ..Default::default()
+            },
+            self.image_size[0] as DeviceSize * self.image_size[1] as DeviceSize,
+        )
+        .unwrap();
 
-        let command_buffer = builder.build().unwrap();
-        let after_future = before_future
-            .then_execute(self.compute_queue.clone(), command_buffer)
-            .unwrap()
-            .then_signal_fence_and_flush()
-            .unwrap()
-            .boxed();
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.compute_queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        ).unwrap();
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(self.image.image().clone(), staging.clone()))
+            .unwrap();
+        self.submit_and_wait(builder);
 
-        after_future
+        // TRAIL_FORMAT is half floats
+        let pixels = staging.read().unwrap();
+        pixels.iter().map(|pixel| pixel.map(|bits| f16::from_bits(bits).to_f32())).collect()
+    }
+
+    /// Blocks until every step submitted so far has finished.
+    pub fn wait(&self) {
+        if let Some(fence) = &self.last_submission {
+            fence.wait(None).unwrap();
+        }
+    }
+
+    fn submit_and_wait(
+        &mut self,
+        builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer, Arc<StandardCommandBufferAllocator>>,
+    ) {
+        self.submit(builder).wait(None).unwrap();
     }
 
-    pub fn compute(
+    /// Submits after the previous submission, so it is ordered after everything already sent.
+    fn submit(
         &mut self,
-        before_future: Box<dyn GpuFuture>
-    ) -> Box<dyn GpuFuture> {
+        builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer, Arc<StandardCommandBufferAllocator>>,
+    ) -> Arc<FenceSignalFuture<Box<dyn GpuFuture>>> {
+        let before_future = match self.last_submission.take() {
+            Some(mut fence) => {
+                fence.cleanup_finished();
+                fence.boxed()
+            }
+            None => sync::now(self.compute_queue.device().clone()).boxed(),
+        };
+
+        let command_buffer = builder.build().unwrap();
+        let fence = Arc::new(
+            before_future
+                .then_execute(self.compute_queue.clone(), command_buffer)
+                .unwrap()
+                .boxed()
+                .then_signal_fence_and_flush()
+                .unwrap(),
+        );
+        self.last_submission = Some(fence.clone());
+        fence
+    }
+
+    /// A future for the render pass to follow on from, through a semaphore if compute runs on its
+    /// own queue.
+    fn for_render(&self, fence: Arc<FenceSignalFuture<Box<dyn GpuFuture>>>) -> Box<dyn GpuFuture> {
+        if self.separate_queue {
+            fence.then_signal_semaphore_and_flush().unwrap().boxed()
+        } else {
+            fence.boxed()
+        }
+    }
+
+    /// The trail as of the last step submitted in frame slot `frame`.
+    pub fn colour_image(&self, frame: usize) -> DeviceImageView {
+        self.display_images[frame].clone()
+    }
+
+    /// Rolling average GPU time of each compute pass in milliseconds, empty unless profiling.
+    pub fn pass_timings(&self) -> Vec<(&'static str, f32)> {
+        self.timer.as_ref().map(GpuTimer::averages).unwrap_or_default()
+    }
+
+    /// Clears the trail, the returned future has to come before anything reading the display
+    /// image for frame slot `frame`.
+    pub fn init(&mut self, frame: usize) -> Box<dyn GpuFuture> {
         let mut builder = AutoCommandBufferBuilder::primary(
             &self.command_buffer_allocator,
             self.compute_queue.queue_family_index(),
             CommandBufferUsage::OneTimeSubmit,
         ).unwrap();
 
+        builder.fill_buffer(self.deposit_buffer.clone(), 0).unwrap();
+        self.dispatch(&mut builder, &self.init_pass);
+        self.copy_to_display(&mut builder, frame);
 
-        self.dispatch(&mut builder, 1, ((self.num_agents - 1) / 64 as u32) * 64 + 64);
-        self.dispatch(&mut builder, 2, ((NUM_PIXELS - 1) / 64 as u32) * 64 + 64);
+        let fence = self.submit(builder);
+        self.for_render(fence)
+    }
 
-        let command_buffer = builder.build().unwrap();
-        let after_future = before_future
-            .then_execute(self.compute_queue.clone(), command_buffer)
-            .unwrap()
-            .then_signal_fence_and_flush()
-            .unwrap()
-            .boxed();
+    /// Advances the simulation by `delta_time` seconds and copies the result into the display image
+    /// for frame slot `frame`. The caller must have waited for that slot's previous frame.
+    ///
+    /// The step doesn't wait on anything from the render queue, so it can run while earlier frames
+    /// are still drawing. The returned future has to come before reading the display image.
+    pub fn compute(&mut self, delta_time: f32, frame: usize) -> Box<dyn GpuFuture> {
+        // keyframed parameters follow the step count rather than the clock so renders repeat exactly
+        if !self.scene.tracks.is_empty() {
+            let mut params = self.params;
+            animate(&self.scene.tracks, self.frame, &mut params);
+            self.set_params(params);
+        }
+        self.delta_time = delta_time;
+        // the slot's last step has finished, so its count is the newest one the host can see
+        if !self.reset_counts && !self.stale_counts[frame] {
+            if let Ok(alive) = self.count_readback[frame].read() {
+                self.num_agents = *alive;
+            }
+        }
+        if let Some(stats) = &mut self.stats {
+            stats.collect(frame, self.image_size);
+        }
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.compute_queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        ).unwrap();
+
+        if let Some(timer) = &mut self.timer {
+            timer.reset(&mut builder, frame);
+        }
+
+        let emitters = self.emit(delta_time);
+        self.num_emitted = emitters.iter().map(|emitter| emitter.count).sum();
+        if self.num_emitted > 0 {
+            self.emit_seed = self.rng.gen();
+        }
+
+        // the count lags a few frames, so leave room for a growing population to split into
+        let mut needed = self.num_agents + self.pending.len() as u32 + self.num_emitted;
+        if self.params.split_energy > 0.0 && needed >= self.agent_capacity / 4 * 3 {
+            needed = needed.max(self.agent_capacity + 1);
+        }
+        self.reserve_agents(&mut builder, needed);
+        self.num_spawned = self.upload_pending(&mut builder);
+        if self.num_emitted > 0 {
+            builder
+                .update_buffer(self.emitter_buffer.clone(), emitters.into_boxed_slice())
+                .unwrap();
+        }
+
+        if self.reset_counts {
+            builder
+                .update_buffer(
+                    self.agent_counts.clone(),
+                    Box::new(slime_shader::AgentCounts { alive: 0, next_alive: 0 }),
+                )
+                .unwrap();
+            self.reset_counts = false;
+        }
+
+        if self.settings_dirty {
+            builder
+                .update_buffer(self.settings_buffer.clone(), Box::new(self.settings()))
+                .unwrap();
+            self.settings_dirty = false;
+        }
+
+        if self.clear_deposits {
+            builder.fill_buffer(self.deposit_buffer.clone(), 0).unwrap();
+            self.clear_deposits = false;
+        }
 
+        if self.stats.is_some() {
+            builder
+                .update_buffer(
+                    self.stats_counts.clone(),
+                    Box::new(slime_shader::StatsCounts {
+                        max_strength: 0,
+                        covered: 0,
+                        histogram: [0; HISTOGRAM_BINS],
+                        headings: [0; HEADING_BINS],
+                    }),
+                )
+                .unwrap();
+        }
+
+        for (index, pass) in self.passes.iter().enumerate() {
+            if let Some(timer) = &self.timer {
+                timer.begin(&mut builder, index);
+            }
+            if !pass.stats || self.stats.is_some() {
+                self.dispatch(&mut builder, pass);
+            }
+            if let Some(timer) = &self.timer {
+                timer.end(&mut builder, index);
+            }
+        }
+        self.finish_agents(&mut builder, frame);
+        self.copy_to_display(&mut builder, frame);
+        if let Some(stats) = &mut self.stats {
+            builder
+                .copy_buffer(CopyBufferInfo::buffers(self.stats_counts.clone(), stats.counts[frame].clone()))
+                .unwrap()
+                .copy_buffer(CopyBufferInfo::buffers(self.stats_sums.clone(), stats.sums[frame].clone()))
+                .unwrap();
+            stats.frames[frame] = Some(self.frame);
+        }
+
+        let fence = self.submit(builder);
+        self.frame = self.frame.wrapping_add(1);
+        self.num_agents = (self.num_agents + self.num_spawned + self.num_emitted).min(self.max_alive.unwrap_or(u32::MAX));
+        self.max_alive = None;
+        let after_future = self.for_render(fence);
         after_future
 
     }
 
+    /// Works out how many agents each emitter adds over `delta_time`, carrying the fractions over
+    /// to later steps, and where they go in the emit pass's output.
+    fn emit(&mut self, delta_time: f32) -> Vec<slime_shader::Emitter> {
+        let mut first = 0;
+        self.scene
+            .emitters
+            .iter()
+            .zip(&mut self.emitter_carry)
+            .map(|(emitter, carry)| {
+                *carry += emitter.rate * delta_time;
+                let count = *carry as u32;
+                *carry -= count as f32;
+                let emitter = slime_shader::Emitter {
+                    pos: emitter.pos,
+                    radius: emitter.radius,
+                    direction: emitter.direction,
+                    spread: emitter.spread,
+                    species: emitter.species,
+                    first,
+                    count,
+                };
+                first += count;
+                emitter
+            })
+            .collect()
+    }
+
+    /// Makes this step's survivors and offspring the live agents if the agents were compacted, and
+    /// copies the live count out to be read back once frame slot `frame` comes round again.
+    fn finish_agents(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer, Arc<StandardCommandBufferAllocator>>,
+        frame: usize,
+    ) {
+        let counts = self.agent_counts.as_bytes();
+        if self.compacts() {
+            builder
+                .copy_buffer(CopyBufferInfo {
+                    regions: [BufferCopy {
+                        src_offset: 4,
+                        dst_offset: 0,
+                        size: 4,
+                        ..Default::default()
+                    }]
+                    .into(),
+                    ..CopyBufferInfo::buffers(counts.clone(), counts.clone())
+                })
+                .unwrap();
+            self.live_buffer = 1 - self.live_buffer;
+        }
+        builder
+            .copy_buffer(CopyBufferInfo {
+                regions: [BufferCopy {
+                    size: 4,
+                    ..Default::default()
+                }]
+                .into(),
+                ..CopyBufferInfo::buffers(counts.clone(), self.count_readback[frame].clone())
+            })
+            .unwrap();
+        self.stale_counts[frame] = false;
+    }
+
+    /// Whether agents can die or split, which has the update pass write the survivors to the other
+    /// agent buffer. Otherwise every agent is updated where it is, so they keep the order they were
+    /// added in rather than whatever order the compaction's atomics happen to give.
+    fn compacts(&self) -> bool {
+        self.params.max_age > 0.0
+            || self.params.energy_cost > 0.0
+            || self.params.split_energy > 0.0
+            || !self.scene.sinks.is_empty()
+    }
+
+    fn copy_to_display(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer, Arc<StandardCommandBufferAllocator>>,
+        frame: usize,
+    ) {
+        builder
+            .copy_image(CopyImageInfo::images(
+                self.image.image().clone(),
+                self.display_images[frame].image().clone(),
+            ))
+            .unwrap();
+    }
+
+    fn settings(&self) -> slime_shader::SimSettings {
+        // the first species lays the trail colour down
+        let mut species_colours = [[0.0; 4]; MAX_SPECIES];
+        for (colour, [r, g, b]) in species_colours.iter_mut().zip([self.params.trail_colour].into_iter().chain(SPECIES_COLOURS)) {
+            *colour = [r, g, b, 1.0];
+        }
+        slime_shader::SimSettings {
+            species_colours,
+
+            width: self.image_size[0] as i32 - 2,
+            height: self.image_size[1] as i32 - 2,
+
+            turn_speed: self.params.turn_speed,
+            move_speed: self.params.move_speed,
+            sense_distance: self.params.sense_distance,
+            sensor_angle: self.params.sensor_angle,
+
+            decay_rate: self.params.decay_rate,
+            diffuse_rate: self.params.diffuse_rate,
+            deposit_amount: self.params.deposit_amount,
+
+            initial_energy: INITIAL_ENERGY,
+            max_age: self.params.max_age,
+            energy_gain: self.params.energy_gain,
+            energy_cost: self.params.energy_cost,
+            split_energy: self.params.split_energy,
+
+            num_sinks: self.scene.sinks.len() as u32,
+            num_foods: self.scene.foods.len() as u32,
+            num_species: self.num_species,
+            compact_agents: self.compacts() as u32,
+        }
+    }
+
+    fn push_constants(&self) -> slime_shader::PushConstants {
+        slime_shader::PushConstants {
+            delta_time: self.delta_time,
+            frame: self.frame,
+            num_spawned: self.num_spawned,
+            capacity: self.agent_capacity,
+            max_alive: self.max_alive.unwrap_or(u32::MAX),
+            num_emitted: self.num_emitted,
+            num_emitters: self.scene.emitters.len() as u32,
+            emit_seed: self.emit_seed,
+        }
+    }
+
     fn dispatch(
         &self,
         builder: &mut AutoCommandBufferBuilder<
         PrimaryAutoCommandBuffer,
         Arc<StandardCommandBufferAllocator>>,
-        step: i32,
-        num_to_process: u32,
+        pass: &ComputePass,
     ) {
-        let pipeline_layout = self.compute_pipeline.layout();
-        let desc_layout = pipeline_layout.set_layouts().get(0).unwrap();
-        let set = PersistentDescriptorSet::new(
-            &self.descriptor_set_allocator,
-            desc_layout.clone(),
-            [
-                WriteDescriptorSet::image_view(0, self.image.clone()),
-                WriteDescriptorSet::buffer(1, self.agent_buffer.clone()),
-                // WriteDescriptorSet::buffer(2, self.life_out.clone()),
-            ],
-        )
-        .unwrap();
-
-        let push_constants = slime_shader::PushConstants {
-            step,
-            num_agents: self.num_agents as i32,
-            width: WIDTH as i32 / SCALE as i32 - 2,
-            height: HEIGHT as i32 / SCALE as i32 - 2,
-
-            turn_speed: TURN_SPEED,
-            move_speed: MOVE_SPEED,
-            sense_distance: SENSE_DISTANCE,
-            sensor_angle: SENSE_ANGLE,
-            sensor_size: SENSE_SIZE,
-
-            decay_rate: DECAY_RATE,
-            diffuse_rate: DIFFUSE_RATE,
+        let pipeline_layout = pass.pipeline.layout();
+        let num_agents = match pass.domain {
+            PassDomain::Spawned => self.num_spawned,
+            PassDomain::Emitted => self.num_emitted,
+            _ => 0,
         };
-        builder
-            .bind_pipeline_compute(self.compute_pipeline.clone())
-            .bind_descriptor_sets(PipelineBindPoint::Compute, pipeline_layout.clone(), 0, set)
-            .push_constants(pipeline_layout.clone(), 0, push_constants)
-            .dispatch([num_to_process / 64, 1, 1])
+        if matches!(pass.domain, PassDomain::Spawned | PassDomain::Emitted) && num_agents == 0 {
+            return;
+        }
+        let group_counts = pass
+            .group_counts(self.image_size, num_agents, self.max_group_counts)
             .unwrap();
+
+        trace!("dispatch {}: {group_counts:?} groups of {:?}", pass.name, pass.local_size);
+        builder
+            .bind_pipeline_compute(pass.pipeline.clone())
+            .bind_descriptor_sets(PipelineBindPoint::Compute, pipeline_layout.clone(), 0, pass.descriptor_sets[self.live_buffer].clone());
+        // init and the horizontal blur don't read any per step values so have no push constant range
+        if !pipeline_layout.push_constant_ranges().is_empty() {
+            builder.push_constants(pipeline_layout.clone(), 0, self.push_constants());
+        }
+        if matches!(pass.domain, PassDomain::Agents) {
+            builder
+                .dispatch_indirect(self.dispatch_args.clone())
+                .unwrap();
+        } else {
+            builder
+                .dispatch(group_counts)
+                .unwrap();
+        }
     }
 
 
@@ -0,0 +1,77 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+
+/// Have to match the defines in `common.glsl`.
+pub const HISTOGRAM_BINS: usize = 32;
+pub const HEADING_BINS: usize = 32;
+
+/// Strength a trail pixel needs to count towards the coverage unless told otherwise.
+pub const COVERAGE_THRESHOLD: f32 = 0.1;
+
+
+/// Trail and agent statistics for one step, reduced on the gpu by the stats passes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrailStats {
+    /// Steps since init when these were taken.
+    pub frame: u32,
+    /// Trail strength averaged over every pixel.
+    pub mean: f32,
+    pub max: f32,
+    pub total: f32,
+    /// Fraction of pixels at or above the coverage threshold.
+    pub coverage: f32,
+    /// Pixels by trail strength, in equal bins from 0 to 1.
+    pub histogram: [u32; HISTOGRAM_BINS],
+    /// Live agents going into the step by heading, in equal bins from 0 to 2 pi radians.
+    pub headings: [u32; HEADING_BINS],
+}
+
+impl TrailStats {
+    /// Agents counted by the heading pass.
+    pub fn agents(&self) -> u32 {
+        self.headings.iter().sum()
+    }
+}
+
+
+/// Writes a row of [`TrailStats`] per step to a CSV file.
+pub struct StatsLog {
+    writer: BufWriter<File>,
+    last_frame: Option<u32>,
+}
+
+impl StatsLog {
+    /// Creates the file, replacing any already there, and writes the header.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<StatsLog> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(writer, "frame,mean,max,total,coverage,agents")?;
+        for bin in 0..HISTOGRAM_BINS {
+            write!(writer, ",histogram_{bin}")?;
+        }
+        for bin in 0..HEADING_BINS {
+            write!(writer, ",heading_{bin}")?;
+        }
+        writeln!(writer)?;
+        Ok(StatsLog { writer, last_frame: None })
+    }
+
+    /// Appends a row, unless the step has already been written.
+    pub fn record(&mut self, stats: &TrailStats) -> io::Result<()> {
+        if self.last_frame == Some(stats.frame) {
+            return Ok(());
+        }
+        self.last_frame = Some(stats.frame);
+
+        write!(
+            self.writer,
+            "{},{},{},{},{},{}",
+            stats.frame, stats.mean, stats.max, stats.total, stats.coverage, stats.agents()
+        )?;
+        for count in stats.histogram.iter().chain(&stats.headings) {
+            write!(self.writer, ",{count}")?;
+        }
+        writeln!(self.writer)
+    }
+}